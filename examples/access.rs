@@ -10,12 +10,7 @@ async fn main() -> Result<()> {
         if crbinfo.cluster_role_info.metadata.name == Some("csr".to_string()) {
             println!("cluster_role_info: {:#?}", crbinfo.cluster_role_info);
             println!("------------------------------------");
-            println!(
-                "cluster_role_resources: {:#?}",
-                crbinfo.cluster_role_resources
-            );
-            println!("------------------------------------");
-            println!("cluster_role_verbs: {:#?}", crbinfo.cluster_role_verbs);
+            println!("cluster_role_rules: {:#?}", crbinfo.cluster_role_rules);
         }
     }
 
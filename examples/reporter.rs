@@ -0,0 +1,39 @@
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+use kube::Client;
+use rbacr::{Access, Check, Finding, Reporter, ScanInfo};
+
+/// Counts the subjects each check found, e.g. to feed a dashboard.
+#[derive(Default)]
+struct CountReporter {
+    counts: BTreeMap<String, usize>,
+}
+
+impl Reporter for CountReporter {
+    fn finding(&mut self, finding: &Finding) -> Result<()> {
+        *self.counts.entry(finding.check.clone()).or_default() += finding.subjects.len();
+        Ok(())
+    }
+
+    fn end(&mut self, info: &ScanInfo) -> Result<()> {
+        println!("{} at {}", info.target, info.started);
+        for (check, count) in &self.counts {
+            println!("{count:>5} {check}");
+        }
+        Ok(())
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let client = Client::try_default().await?;
+    let access = Access::run(client).await?;
+    let check = Check::from_yaml(include_str!("../fixtures/check.yaml"))?;
+
+    check
+        .report(&access, "current-context", &mut CountReporter::default())
+        .await?;
+
+    Ok(())
+}
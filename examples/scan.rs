@@ -0,0 +1,26 @@
+use anyhow::Result;
+use kube::Client;
+use rbacr::{Access, Check, Scope, Severity};
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let client = Client::try_default().await?;
+    let access = Access::run(client).await?;
+    let check = Check::from_yaml(include_str!("../fixtures/check.yaml"))?;
+
+    let findings = check.run(&access).await?;
+    for finding in findings
+        .iter()
+        .filter(|finding| finding.severity >= Severity::High)
+    {
+        match &finding.scope {
+            Scope::Cluster => println!("[{}] cluster-wide: {}", finding.check, finding.summary()),
+            Scope::Namespace(ns) => {
+                println!("[{}] in {}: {}", finding.check, ns, finding.summary())
+            }
+        }
+        println!("matched rules: {:#?}", finding.rules);
+    }
+
+    Ok(())
+}
@@ -1,19 +1,47 @@
+use std::collections::{BTreeSet, HashMap};
+
 use anyhow::Result;
 use k8s_openapi::api::{
     core::v1::Namespace,
-    rbac::v1::{ClusterRole, ClusterRoleBinding, Role, RoleBinding},
+    rbac::v1::{ClusterRole, ClusterRoleBinding, PolicyRule, Role, RoleBinding, Subject},
 };
 use kube::{
     api::{Api, ListParams, ResourceExt},
     Client,
 };
+use log::info;
 use serde::{Deserialize, Serialize};
 
+use crate::{finding::ObjectRef, workload::Workload};
+
+/// The label the API server puts on the default RBAC objects it bootstraps.
+const BOOTSTRAPPING_LABEL: &str = "kubernetes.io/bootstrapping";
+const BOOTSTRAPPING_DEFAULTS: &str = "rbac-defaults";
+/// Names of the control plane's users and groups like `system:masters`.
+const SYSTEM_PREFIX: &str = "system:";
+/// `system:` identities standing for anyone or for whole sets of
+/// ServiceAccounts, bindings to them are never system.
+const BROAD_SUBJECTS: [&str; 4] = [
+    "system:anonymous",
+    "system:unauthenticated",
+    "system:authenticated",
+    "system:serviceaccounts",
+];
+/// The group of the ServiceAccounts of one namespace, `system:serviceaccounts:<ns>`.
+const NAMESPACE_SERVICE_ACCOUNTS: &str = "system:serviceaccounts:";
+/// A ServiceAccount bound as a user, `system:serviceaccount:<ns>:<name>`.
+const SERVICE_ACCOUNT_USER: &str = "system:serviceaccount:";
+/// The namespace of the control plane's own ServiceAccounts.
+const SYSTEM_NAMESPACE: &str = "kube-system";
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Access {
     pub ns_list: NsAll,
     pub rbinfo_list: Vec<RBInfo>,
     pub crbinfo_list: Vec<CRBInfo>,
+    /// Empty unless workloads were listed or found in the manifests.
+    #[serde(default)]
+    pub workloads: Vec<Workload>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -25,122 +53,332 @@ pub struct NsAll {
 pub struct RBInfo {
     pub role_binding_info: RoleBinding,
     pub role_info: Role,
-    pub role_resources: Option<Vec<String>>,
-    pub role_verbs: Vec<String>,
+    pub role_rules: Vec<RuleInfo>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<Source>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct CRBInfo {
     pub cluster_role_binding_info: ClusterRoleBinding,
     pub cluster_role_info: ClusterRole,
-    pub cluster_role_resources: Option<Vec<String>>,
-    pub cluster_role_verbs: Vec<String>,
+    pub cluster_role_rules: Vec<RuleInfo>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<Source>,
+}
+
+/// A single `PolicyRule` of a role, kept intact so that its verbs only ever
+/// apply to its own apiGroups, resources and resourceNames.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct RuleInfo {
+    pub api_groups: Vec<String>,
+    pub resources: Vec<String>,
+    pub verbs: Vec<String>,
+    pub resource_names: Vec<String>,
+    pub non_resource_urls: Vec<String>,
+    /// The ClusterRole an aggregated ClusterRole took the rule from.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_role: Option<String>,
+}
+
+/// Where an object was read from when scanning manifests.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Source {
+    pub file: String,
+    /// 1-based line the object starts at.
+    pub line: usize,
+}
+
+/// The RBAC objects of a cluster, as listed from the API server or read from
+/// manifests, before bindings are resolved to their roles.
+#[derive(Debug, Default)]
+pub struct Objects {
+    pub namespaces: Vec<Namespace>,
+    pub roles: Vec<Role>,
+    pub role_bindings: Vec<RoleBinding>,
+    pub cluster_roles: Vec<ClusterRole>,
+    pub cluster_role_bindings: Vec<ClusterRoleBinding>,
+    /// Only known for objects read from manifest files.
+    pub sources: HashMap<ObjectRef, Source>,
+    /// The resolved rules of aggregated ClusterRoles by name, see `aggregate`.
+    pub aggregated_rules: HashMap<String, Vec<RuleInfo>>,
+    /// Only listed from a cluster by `list_workloads`.
+    pub workloads: Vec<Workload>,
 }
 
 impl Access {
     pub async fn run(client: Client) -> Result<Self> {
-        let ns_all = NsAll::run(client.clone()).await?;
-        let rbinfo_list = RBInfo::run(client.clone(), ns_all.clone()).await?;
-        let crbinfo_list = CRBInfo::run(client.clone()).await?;
+        let objects = Objects::run(client).await?;
+        Ok(Self::from_objects(objects))
+    }
+
+    /// Like [`Access::run`], also listing the workloads that run as the
+    /// ServiceAccounts of the bindings.
+    pub async fn run_with_workloads(client: Client) -> Result<Self> {
+        let mut objects = Objects::run(client.clone()).await?;
+        objects.list_workloads(client).await?;
+        Ok(Self::from_objects(objects))
+    }
+
+    /// Build the same structure as [`Access::run`] without a cluster, from a
+    /// manifest file, a directory of manifests or `-` for stdin.
+    pub async fn from_manifests(path: &str) -> Result<Self> {
+        let objects = Objects::load(path).await?;
+        Ok(Self::from_objects(objects))
+    }
+
+    pub fn from_objects(mut objects: Objects) -> Self {
+        objects.aggregate();
+        Self {
+            ns_list: NsAll::from_objects(&objects),
+            rbinfo_list: RBInfo::from_objects(&objects),
+            crbinfo_list: CRBInfo::from_objects(&objects),
+            workloads: objects.workloads,
+        }
+    }
+
+    /// Drop the RoleBindings of every other namespace, ClusterRoleBindings
+    /// still apply to this one.
+    pub fn retain_namespace(&mut self, ns: &str) {
+        self.ns_list.ns_all.retain(|name| name == ns);
+        self.rbinfo_list
+            .retain(|rbinfo| rbinfo.role_binding_info.namespace().as_deref() == Some(ns));
+    }
+
+    /// Drop the default bootstrap bindings and the `system:` subjects of the
+    /// others, bindings left without subjects go as well.
+    pub fn exclude_system(&mut self) {
+        for rbinfo in &mut self.rbinfo_list {
+            retain_subjects(&mut rbinfo.role_binding_info.subjects);
+        }
+        for crbinfo in &mut self.crbinfo_list {
+            retain_subjects(&mut crbinfo.cluster_role_binding_info.subjects);
+        }
+        self.rbinfo_list.retain(|rbinfo| {
+            let rb = &rbinfo.role_binding_info;
+            !is_system_binding(rb) && !matches!(&rb.subjects, Some(subjects) if subjects.is_empty())
+        });
+        self.crbinfo_list.retain(|crbinfo| {
+            let crb = &crbinfo.cluster_role_binding_info;
+            !is_system_binding(crb)
+                && !matches!(&crb.subjects, Some(subjects) if subjects.is_empty())
+        });
+    }
+}
+
+/// Bindings labelled `kubernetes.io/bootstrapping=rbac-defaults`. The name is
+/// not enough, anyone allowed to create bindings can call one `system:*`.
+pub fn is_system_binding<K: ResourceExt>(binding: &K) -> bool {
+    binding
+        .labels()
+        .get(BOOTSTRAPPING_LABEL)
+        .map(String::as_str)
+        == Some(BOOTSTRAPPING_DEFAULTS)
+}
+
+/// `system:` users and groups, except those in [`BROAD_SUBJECTS`] and the
+/// ServiceAccounts of a namespace. A single ServiceAccount bound as a user is
+/// only system in `kube-system`.
+pub(crate) fn is_system_subject(name: &str) -> bool {
+    if let Some(account) = name.strip_prefix(SERVICE_ACCOUNT_USER) {
+        return account.split(':').next() == Some(SYSTEM_NAMESPACE);
+    }
+    name.starts_with(SYSTEM_PREFIX)
+        && !BROAD_SUBJECTS.contains(&name)
+        && !name.starts_with(NAMESPACE_SERVICE_ACCOUNTS)
+}
+
+fn retain_subjects(subjects: &mut Option<Vec<Subject>>) {
+    if let Some(subjects) = subjects {
+        subjects.retain(|subject| !is_system_subject(&subject.name));
+    }
+}
+
+impl Objects {
+    pub async fn run(client: Client) -> Result<Self> {
+        let list_params = ListParams::default();
+        let ns_api: Api<Namespace> = Api::all(client.clone());
+        let role_api: Api<Role> = Api::all(client.clone());
+        let rb_api: Api<RoleBinding> = Api::all(client.clone());
+        let cr_api: Api<ClusterRole> = Api::all(client.clone());
+        let crb_api: Api<ClusterRoleBinding> = Api::all(client);
         Ok(Self {
-            ns_list: ns_all,
-            rbinfo_list,
-            crbinfo_list,
+            namespaces: ns_api.list(&list_params).await?.items,
+            roles: role_api.list(&list_params).await?.items,
+            role_bindings: rb_api.list(&list_params).await?.items,
+            cluster_roles: cr_api.list(&list_params).await?.items,
+            cluster_role_bindings: crb_api.list(&list_params).await?.items,
+            ..Default::default()
         })
     }
 }
 
+impl Objects {
+    pub fn source<K: ResourceExt>(&self, kind: &str, object: &K) -> Option<Source> {
+        self.sources.get(&ObjectRef::new(kind, object)).cloned()
+    }
+}
+
 impl NsAll {
-    pub async fn run(client: Client) -> Result<Self> {
-        let ns_api: Api<Namespace> = Api::all(client);
-        let ns_list = ns_api.list(&ListParams::default()).await?;
-        let mut ns_all: Vec<String> = Vec::new();
-        for ns in ns_list {
-            ns_all.push(ns.name_any());
+    /// Namespaces that exist or that hold roles and bindings, manifests often
+    /// don't include the Namespace objects themselves.
+    pub fn from_objects(objects: &Objects) -> Self {
+        let ns_all: BTreeSet<String> = objects
+            .namespaces
+            .iter()
+            .map(|ns| ns.name_any())
+            .chain(objects.roles.iter().filter_map(|role| role.namespace()))
+            .chain(objects.role_bindings.iter().filter_map(|rb| rb.namespace()))
+            .collect();
+        Self {
+            ns_all: ns_all.into_iter().collect(),
         }
-        Ok(Self { ns_all })
     }
 }
 
 impl RBInfo {
-    pub async fn run(client: Client, ns_all: NsAll) -> Result<Vec<Self>> {
+    pub fn from_objects(objects: &Objects) -> Vec<Self> {
         let mut rbinfo_list: Vec<Self> = Vec::new();
-        for ns in ns_all.ns_all {
-            let rb_api: Api<RoleBinding> = Api::namespaced(client.clone(), &ns);
-            let list_params = ListParams::default();
-            let rb_list = rb_api.list(&list_params).await?;
-            for rb in rb_list {
-                let role_api: Api<Role> = Api::namespaced(client.clone(), &ns);
-                let role_name = &rb.role_ref.name;
-                let role = match role_api.get(&role_name).await {
-                    Ok(role) => role,
-                    Err(_) => continue,
-                };
-                rbinfo_list.push(Self {
-                    role_binding_info: rb,
-                    role_info: role.clone(),
-                    role_resources: role.rules.as_ref().map(|rules| {
-                        rules
-                            .iter()
-                            .map(|rule| rule.resources.as_ref().unwrap().clone())
-                            .flatten()
-                            .collect()
+        for rb in &objects.role_bindings {
+            let ns = rb.namespace().unwrap_or("default".to_string());
+            let role_name = &rb.role_ref.name;
+            let role = match rb.role_ref.kind.as_str() {
+                // A RoleBinding may grant the rules of a ClusterRole inside its own namespace.
+                "ClusterRole" => objects
+                    .cluster_roles
+                    .iter()
+                    .find(|cr| &cr.name_any() == role_name)
+                    .map(|cr| {
+                        let rules = objects
+                            .cluster_role_rules(cr)
+                            .into_iter()
+                            .filter(|rule| !rule.resources.is_empty())
+                            .map(|rule| RuleInfo {
+                                non_resource_urls: Vec::new(),
+                                ..rule
+                            })
+                            .collect();
+                        (role_from_cluster_role(cr.clone(), &ns), rules)
                     }),
-                    role_verbs: role
-                        .rules
-                        .as_ref()
-                        .map(|rules| {
-                            rules
-                                .iter()
-                                .map(|rule| rule.verbs.clone())
-                                .flatten()
-                                .collect()
-                        })
-                        .unwrap_or_default(),
-                });
-            }
+                _ => objects
+                    .roles
+                    .iter()
+                    .find(|role| {
+                        &role.name_any() == role_name
+                            && role.namespace().unwrap_or("default".to_string()) == ns
+                    })
+                    .map(|role| (role.clone(), RuleInfo::from_rules(&role.rules))),
+            };
+            let (role, role_rules) = match role {
+                Some(role) => role,
+                None => {
+                    info!(
+                        "RoleBinding: \"{}\" references missing {} \"{}\"",
+                        rb.name_any(),
+                        rb.role_ref.kind,
+                        role_name
+                    );
+                    continue;
+                }
+            };
+            rbinfo_list.push(Self {
+                role_binding_info: rb.clone(),
+                role_rules,
+                role_info: role,
+                source: objects.source("RoleBinding", rb),
+            });
         }
-        Ok(rbinfo_list)
+        rbinfo_list
     }
 }
 
 impl CRBInfo {
-    pub async fn run(client: Client) -> Result<Vec<Self>> {
+    pub fn from_objects(objects: &Objects) -> Vec<Self> {
         let mut crbinfo_list: Vec<Self> = Vec::new();
-        let crb_api: Api<ClusterRoleBinding> = Api::all(client.clone());
-        let list_params = ListParams::default();
-        let crb_list = crb_api.list(&list_params).await?;
-        for crb in crb_list {
-            let cr_api: Api<ClusterRole> = Api::all(client.clone());
+        for crb in &objects.cluster_role_bindings {
             let cr_name = &crb.role_ref.name;
-            let cr = match cr_api.get(&cr_name).await {
-                Ok(cr) => cr,
-                Err(_) => continue,
+            let cr = match objects
+                .cluster_roles
+                .iter()
+                .find(|cr| &cr.name_any() == cr_name)
+            {
+                Some(cr) => cr.clone(),
+                None => {
+                    info!(
+                        "ClusterRoleBinding: \"{}\" references missing ClusterRole \"{}\"",
+                        crb.name_any(),
+                        cr_name
+                    );
+                    continue;
+                }
             };
             crbinfo_list.push(Self {
-                cluster_role_binding_info: crb,
-                cluster_role_info: cr.clone(),
-                cluster_role_resources: cr.rules.as_ref().map(|rules| {
-                    rules
-                        .iter()
-                        .flat_map(|rule| rule.resources.clone())
-                        .flatten()
-                        .collect()
-                }),
-                cluster_role_verbs: cr
-                    .rules
-                    .as_ref()
-                    .map(|rules| {
-                        rules
-                            .iter()
-                            .map(|rule| rule.verbs.clone())
-                            .flatten()
-                            .collect()
-                    })
-                    .unwrap_or_default(),
+                cluster_role_binding_info: crb.clone(),
+                cluster_role_rules: objects.cluster_role_rules(&cr),
+                cluster_role_info: cr,
+                source: objects.source("ClusterRoleBinding", crb),
             });
         }
-        Ok(crbinfo_list)
+        crbinfo_list
+    }
+}
+
+impl RuleInfo {
+    pub fn from_rules(rules: &Option<Vec<PolicyRule>>) -> Vec<Self> {
+        rules
+            .iter()
+            .flatten()
+            .map(|rule| Self {
+                api_groups: rule.api_groups.clone().unwrap_or_default(),
+                resources: rule.resources.clone().unwrap_or_default(),
+                verbs: rule.verbs.clone(),
+                resource_names: rule.resource_names.clone().unwrap_or_default(),
+                non_resource_urls: rule.non_resource_urls.clone().unwrap_or_default(),
+                source_role: None,
+            })
+            .collect()
+    }
+}
+
+/// Scope the rules of a ClusterRole to the namespace of the RoleBinding referencing it.
+/// nonResourceURLs are not namespaced and are only granted through ClusterRoleBindings.
+fn role_from_cluster_role(cr: ClusterRole, ns: &str) -> Role {
+    let mut metadata = cr.metadata;
+    metadata.namespace = Some(ns.to_string());
+    Role {
+        metadata,
+        rules: cr.rules.map(|rules| {
+            rules
+                .into_iter()
+                .filter(|rule| matches!(&rule.resources, Some(resources) if !resources.is_empty()))
+                .map(|rule| PolicyRule {
+                    non_resource_urls: None,
+                    ..rule
+                })
+                .collect()
+        }),
+    }
+}
+
+/// The ClusterRole most tests bind, it may `get` and `list` secrets.
+#[cfg(test)]
+const SECRET_READER: &str = r#"apiVersion: rbac.authorization.k8s.io/v1
+kind: ClusterRole
+metadata:
+  name: secret-reader
+rules:
+  - {apiGroups: [""], resources: ["secrets"], verbs: ["get", "list"]}
+"#;
+
+#[cfg(test)]
+impl Access {
+    /// The `secret-reader` ClusterRole together with the objects of `manifest`.
+    pub(crate) fn with_secret_reader(manifest: &str, file: Option<&str>) -> Self {
+        let mut objects = Objects::default();
+        objects
+            .add_manifest(&format!("{SECRET_READER}---\n{manifest}"), file)
+            .unwrap();
+        Self::from_objects(objects)
     }
 }
 
@@ -166,4 +404,110 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn test_exclude_system() {
+        let mut objects = Objects::default();
+        objects
+            .add_manifest(
+                r#"
+apiVersion: rbac.authorization.k8s.io/v1
+kind: ClusterRoleBinding
+metadata:
+  name: cluster-admin
+  labels:
+    kubernetes.io/bootstrapping: rbac-defaults
+roleRef: {apiGroup: rbac.authorization.k8s.io, kind: ClusterRole, name: admin}
+subjects:
+  - {kind: Group, name: admins}
+---
+apiVersion: rbac.authorization.k8s.io/v1
+kind: ClusterRoleBinding
+metadata:
+  name: ops
+roleRef: {apiGroup: rbac.authorization.k8s.io, kind: ClusterRole, name: admin}
+subjects:
+  - {kind: Group, name: "system:masters"}
+  - {kind: User, name: bob}
+---
+apiVersion: rbac.authorization.k8s.io/v1
+kind: ClusterRoleBinding
+metadata:
+  name: nodes
+roleRef: {apiGroup: rbac.authorization.k8s.io, kind: ClusterRole, name: admin}
+subjects:
+  - {kind: Group, name: "system:nodes"}
+---
+apiVersion: rbac.authorization.k8s.io/v1
+kind: ClusterRole
+metadata:
+  name: admin
+"#,
+                None,
+            )
+            .unwrap();
+        let mut access = Access::from_objects(objects);
+        assert_eq!(access.crbinfo_list.len(), 3);
+
+        access.exclude_system();
+        let names: Vec<String> = access
+            .crbinfo_list
+            .iter()
+            .flat_map(|crbinfo| crbinfo.cluster_role_binding_info.subjects.clone())
+            .flatten()
+            .map(|subject| subject.name)
+            .collect();
+        assert_eq!(names, vec!["bob"]);
+    }
+
+    #[test]
+    fn test_role_binding_to_cluster_role() {
+        let mut objects = Objects::default();
+        objects
+            .add_manifest(
+                r#"
+apiVersion: rbac.authorization.k8s.io/v1
+kind: ClusterRole
+metadata:
+  name: reader
+rules:
+  - {apiGroups: [""], resources: ["pods"], verbs: ["get"]}
+  - {nonResourceURLs: ["/metrics"], verbs: ["get"]}
+---
+apiVersion: rbac.authorization.k8s.io/v1
+kind: Role
+metadata:
+  name: reader
+  namespace: prod
+rules:
+  - {apiGroups: [""], resources: ["secrets"], verbs: ["get"]}
+---
+apiVersion: rbac.authorization.k8s.io/v1
+kind: RoleBinding
+metadata:
+  name: read-pods
+  namespace: ci
+roleRef: {apiGroup: rbac.authorization.k8s.io, kind: ClusterRole, name: reader}
+subjects:
+  - {kind: User, name: alice}
+"#,
+                None,
+            )
+            .unwrap();
+        let access = Access::from_objects(objects);
+
+        assert_eq!(access.rbinfo_list.len(), 1);
+        let rbinfo = &access.rbinfo_list[0];
+        assert_eq!(rbinfo.role_info.name_any(), "reader");
+        assert_eq!(rbinfo.role_info.namespace().as_deref(), Some("ci"));
+        assert_eq!(
+            rbinfo.role_rules,
+            vec![RuleInfo {
+                api_groups: vec!["".to_string()],
+                resources: vec!["pods".to_string()],
+                verbs: vec!["get".to_string()],
+                ..Default::default()
+            }]
+        );
+    }
 }
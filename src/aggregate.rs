@@ -0,0 +1,258 @@
+use std::collections::{BTreeMap, HashMap};
+
+use k8s_openapi::{
+    api::rbac::v1::{ClusterRole, PolicyRule},
+    apimachinery::pkg::apis::meta::v1::LabelSelector,
+};
+use kube::ResourceExt;
+
+use crate::access::{Objects, RuleInfo};
+
+impl Objects {
+    /// Fill in the rules of ClusterRoles with an `aggregationRule` from the
+    /// ClusterRoles its selectors pick, as the controller manager does. Manifests
+    /// never carry these rules, so offline and live scans only agree this way.
+    /// Rules already stored on the ClusterRole follow the resolved ones, in
+    /// case the roles it selects are not part of the scan.
+    pub fn aggregate(&mut self) {
+        let mut aggregated = HashMap::new();
+        for cr in &self.cluster_roles {
+            if cr.aggregation_rule.is_some() {
+                aggregated.insert(cr.name_any(), self.resolve(cr, &mut Vec::new()));
+            }
+        }
+        for cr in &mut self.cluster_roles {
+            if let Some(rules) = aggregated.get(&cr.name_any()) {
+                cr.rules = Some(rules.iter().map(RuleInfo::to_policy_rule).collect());
+            }
+        }
+        self.aggregated_rules = aggregated;
+    }
+
+    /// The rules of a ClusterRole, those of aggregated ones name the ClusterRole
+    /// they were picked from.
+    pub fn cluster_role_rules(&self, cr: &ClusterRole) -> Vec<RuleInfo> {
+        match self.aggregated_rules.get(&cr.name_any()) {
+            Some(rules) => rules.clone(),
+            None => RuleInfo::from_rules(&cr.rules),
+        }
+    }
+
+    /// `visiting` holds the aggregated ClusterRoles being resolved, which keeps
+    /// roles selecting each other or themselves from looping.
+    fn resolve(&self, cr: &ClusterRole, visiting: &mut Vec<String>) -> Vec<RuleInfo> {
+        let selectors = match &cr.aggregation_rule {
+            Some(rule) => rule.cluster_role_selectors.clone().unwrap_or_default(),
+            None => return RuleInfo::from_rules(&cr.rules),
+        };
+        visiting.push(cr.name_any());
+        let mut rules: Vec<RuleInfo> = Vec::new();
+        for selected in &self.cluster_roles {
+            if visiting.contains(&selected.name_any())
+                || !selectors
+                    .iter()
+                    .any(|selector| selector_matches(selector, selected.labels()))
+            {
+                continue;
+            }
+            for mut rule in self.resolve(selected, visiting) {
+                rule.source_role.get_or_insert_with(|| selected.name_any());
+                if !rules.iter().any(|known| known.same_rule(&rule)) {
+                    rules.push(rule);
+                }
+            }
+        }
+        visiting.pop();
+        for rule in RuleInfo::from_rules(&cr.rules) {
+            if !rules.iter().any(|known| known.same_rule(&rule)) {
+                rules.push(rule);
+            }
+        }
+        rules
+    }
+}
+
+impl RuleInfo {
+    fn to_policy_rule(&self) -> PolicyRule {
+        let optional = |values: &Vec<String>| match values.is_empty() {
+            true => None,
+            false => Some(values.clone()),
+        };
+        PolicyRule {
+            api_groups: optional(&self.api_groups),
+            resources: optional(&self.resources),
+            verbs: self.verbs.clone(),
+            resource_names: optional(&self.resource_names),
+            non_resource_urls: optional(&self.non_resource_urls),
+        }
+    }
+
+    /// Equal apart from where the rule came from.
+    pub(crate) fn same_rule(&self, other: &Self) -> bool {
+        self.api_groups == other.api_groups
+            && self.resources == other.resources
+            && self.verbs == other.verbs
+            && self.resource_names == other.resource_names
+            && self.non_resource_urls == other.non_resource_urls
+    }
+}
+
+/// Every `matchLabels` entry and `matchExpressions` requirement must hold.
+fn selector_matches(selector: &LabelSelector, labels: &BTreeMap<String, String>) -> bool {
+    let labels_match = selector
+        .match_labels
+        .iter()
+        .flatten()
+        .all(|(key, value)| labels.get(key) == Some(value));
+    let expressions_match = selector
+        .match_expressions
+        .iter()
+        .flatten()
+        .all(|requirement| {
+            let values = requirement.values.as_deref().unwrap_or_default();
+            let value = labels.get(&requirement.key);
+            match requirement.operator.as_str() {
+                "In" => matches!(value, Some(value) if values.contains(value)),
+                "NotIn" => !matches!(value, Some(value) if values.contains(value)),
+                "Exists" => value.is_some(),
+                "DoesNotExist" => value.is_none(),
+                _ => false,
+            }
+        });
+    labels_match && expressions_match
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_aggregate() {
+        let mut objects = Objects::default();
+        objects
+            .add_manifest(
+                r#"
+apiVersion: rbac.authorization.k8s.io/v1
+kind: ClusterRole
+metadata:
+  name: admin
+aggregationRule:
+  clusterRoleSelectors:
+    - matchLabels:
+        rbac.authorization.k8s.io/aggregate-to-admin: "true"
+    - matchExpressions:
+        - {key: example.com/aggregate-to, operator: In, values: [admin, edit]}
+rules: []
+---
+apiVersion: rbac.authorization.k8s.io/v1
+kind: ClusterRole
+metadata:
+  name: edit
+  labels:
+    rbac.authorization.k8s.io/aggregate-to-admin: "true"
+aggregationRule:
+  clusterRoleSelectors:
+    - matchLabels:
+        rbac.authorization.k8s.io/aggregate-to-edit: "true"
+---
+apiVersion: rbac.authorization.k8s.io/v1
+kind: ClusterRole
+metadata:
+  name: secrets-editor
+  labels:
+    rbac.authorization.k8s.io/aggregate-to-edit: "true"
+rules:
+  - apiGroups: [""]
+    resources: ["secrets"]
+    verbs: ["get", "list"]
+---
+apiVersion: rbac.authorization.k8s.io/v1
+kind: ClusterRole
+metadata:
+  name: crd-admin
+  labels:
+    example.com/aggregate-to: admin
+rules:
+  - apiGroups: ["example.com"]
+    resources: ["widgets"]
+    verbs: ["*"]
+  - apiGroups: [""]
+    resources: ["secrets"]
+    verbs: ["get", "list"]
+---
+apiVersion: rbac.authorization.k8s.io/v1
+kind: ClusterRole
+metadata:
+  name: unrelated
+  labels:
+    example.com/aggregate-to: view
+rules:
+  - apiGroups: [""]
+    resources: ["pods"]
+    verbs: ["get"]
+"#,
+                None,
+            )
+            .unwrap();
+        objects.aggregate();
+
+        let admin = objects
+            .cluster_roles
+            .iter()
+            .find(|cr| cr.name_any() == "admin")
+            .unwrap();
+        let rules = objects.cluster_role_rules(admin);
+        let sources: Vec<(&str, &str)> = rules
+            .iter()
+            .map(|rule| {
+                (
+                    rule.resources[0].as_str(),
+                    rule.source_role.as_deref().unwrap(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            sources,
+            vec![("secrets", "secrets-editor"), ("widgets", "crd-admin")]
+        );
+        assert_eq!(admin.rules.as_ref().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_aggregate_keeps_stored_rules() {
+        let mut objects = Objects::default();
+        objects
+            .add_manifest(
+                r#"
+apiVersion: rbac.authorization.k8s.io/v1
+kind: ClusterRole
+metadata:
+  name: monitoring
+aggregationRule:
+  clusterRoleSelectors:
+    - matchLabels:
+        example.com/aggregate-to-monitoring: "true"
+rules:
+  - apiGroups: [""]
+    resources: ["pods"]
+    verbs: ["get", "list"]
+  - apiGroups: [""]
+    resources: ["secrets"]
+    verbs: ["get"]
+"#,
+                None,
+            )
+            .unwrap();
+        objects.aggregate();
+
+        let monitoring = &objects.cluster_roles[0];
+        let rules = objects.cluster_role_rules(monitoring);
+        let resources: Vec<&str> = rules
+            .iter()
+            .map(|rule| rule.resources[0].as_str())
+            .collect();
+        assert_eq!(resources, vec!["pods", "secrets"]);
+        assert!(rules.iter().all(|rule| rule.source_role.is_none()));
+        assert_eq!(monitoring.rules.as_ref().unwrap().len(), 2);
+    }
+}
@@ -1,29 +1,63 @@
-use std::collections::HashMap;
+use std::{collections::BTreeMap, fmt, str::FromStr};
 
 use anyhow::Result;
-use kube::ResourceExt;
+use k8s_openapi::chrono::Utc;
 use log::info;
 use serde::{Deserialize, Serialize};
 use tokio::fs;
 
 use crate::{
-    access::{CRBInfo, RBInfo},
-    print::{output_cr, output_r, SubKind},
-    Access, OutCR, OutR,
+    access::{CRBInfo, RBInfo, RuleInfo},
+    exception::Exceptions,
+    finding::Finding,
+    report::{Reporter, ScanInfo},
+    Access,
 };
 
-#[derive(Serialize, Deserialize, Debug)]
+/// The checks keyed by their id, e.g. `RBACR-001`, which run in id order.
+#[derive(Serialize, Deserialize, Debug, Default)]
 pub struct Check {
-    pub itemlist: HashMap<String, CheckItem>,
+    pub itemlist: BTreeMap<String, CheckItem>,
+    /// Bindings left out of the findings.
+    #[serde(skip)]
+    pub exceptions: Exceptions,
+    /// Keep the findings `exceptions` match, marked as suppressed.
+    #[serde(skip)]
+    pub show_suppressed: bool,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct CheckItem {
+    /// The `itemlist` key of the check.
+    #[serde(skip)]
+    pub id: String,
+    /// Defaults to the id.
+    #[serde(default)]
+    pub title: String,
     // pub namespace: String,
     pub kind: Kind,
+    #[serde(default = "Severity::medium")]
+    pub severity: Severity,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub description: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub remediation: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub references: Vec<String>,
     pub rules: Option<Vec<Rules>>,
 }
 
+/// How bad a match of the check is, ordered from `info` to `critical`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Info,
+    Low,
+    Medium,
+    High,
+    Critical,
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub enum Kind {
     All,
@@ -33,18 +67,52 @@ pub enum Kind {
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Rules {
+    /// API groups the resources belong to, `""` being the core group. Any group
+    /// matches when omitted.
+    #[serde(rename = "apiGroups", default, skip_serializing_if = "Option::is_none")]
+    pub api_groups: Option<Vec<String>>,
     pub resources: Option<Vec<String>>,
     pub verbs: Vec<String>,
+    /// Which `resourceNames` restrictions of a granted rule still satisfy the
+    /// check, only unrestricted grants by default.
+    #[serde(rename = "resourceNames", default)]
+    pub resource_names: ResourceNames,
+    /// Paths such as `/metrics`, only granted by ClusterRoleBindings.
+    #[serde(
+        rename = "nonResourceURLs",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub non_resource_urls: Option<Vec<String>>,
+}
+
+/// Either `unrestricted`, `any` or the list of object names the grant has to cover.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum ResourceNames {
+    Scope(NameScope),
+    Names(Vec<String>),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum NameScope {
+    /// Only rules without `resourceNames` match.
+    Unrestricted,
+    /// Rules match whether or not they are limited to some names.
+    Any,
 }
 
 impl Check {
     pub fn new() -> Self {
-        Self {
-            itemlist: HashMap::new(),
-        }
+        Self::default()
     }
 
-    pub fn add(&mut self, key: String, item: CheckItem) {
+    pub fn add(&mut self, key: String, mut item: CheckItem) {
+        item.id = key.clone();
+        if item.title.is_empty() {
+            item.title = key.clone();
+        }
         // let key = match &item.kind {
         //     Kind::All => "all".to_string(),
         //     Kind::Role => "role".to_string(),
@@ -68,292 +136,300 @@ impl Check {
     }
 
     pub fn get_check(&self) -> Result<Vec<String>> {
-        let mut check_list = Vec::new();
-        for (key, _item) in &self.itemlist {
-            check_list.push(key.to_string());
-        }
-        Ok(check_list)
+        Ok(self.itemlist.keys().cloned().collect())
     }
 
     pub fn get_check_item(&self, key: &str) -> Result<&CheckItem> {
-        let item = self.itemlist.get(key).unwrap();
-        Ok(item)
+        self.itemlist
+            .get(key)
+            .ok_or_else(|| anyhow::anyhow!("Unknown check: {}", key))
     }
 
-    pub async fn run(&self, access: Access) -> Result<()> {
+    /// Run every check and collect the matching bindings, printing is left to
+    /// `report`.
+    pub async fn run(&self, access: &Access) -> Result<Vec<Finding>> {
+        let mut findings = Vec::new();
         for (key, item) in &self.itemlist {
-            info!("Start checking: {}", key);
-            match item.kind {
-                Kind::All => {
-                    let mut out_r_list = Vec::new();
-                    for rbinfo in &access.rbinfo_list {
-                        if Self::check_r(&item.rules, rbinfo).await? {
-                            let rb = &rbinfo.role_binding_info;
-                            match &rb.subjects {
-                                Some(subjects) => {
-                                    let (subject, sub_kind) = {
-                                        let sub_kind_string = subjects[0].kind.to_string();
-                                        let sub_kind = match sub_kind_string.as_str() {
-                                            "User" => SubKind::User,
-                                            "Group" => SubKind::Group,
-                                            "ServiceAccount" => SubKind::ServiceAccount,
-                                            _ => {
-                                                return Err(anyhow::anyhow!(
-                                                    "Unknown subject kind: {}",
-                                                    sub_kind_string
-                                                ))
-                                            }
-                                        };
-                                        (subjects[0].name.clone(), sub_kind)
-                                    };
-                                    let out_r = OutR {
-                                        rb: rb.name_any(),
-                                        ns: rb.namespace().unwrap_or("default".to_string()),
-                                        r: rb.role_ref.name.clone(),
-                                        age: rb.metadata.clone(),
-                                        subject,
-                                        sub_kind,
-                                    };
-                                    out_r_list.push(out_r);
-                                }
-                                None => {
-                                    info!(
-                                        "RoleBinding: \"{}\" does not display without subjects",
-                                        rb.name_any()
-                                    );
-                                }
-                            }
-                        }
-                    }
-                    match out_r_list.len() {
-                        0 => {}
-                        _ => {
-                            output_r(out_r_list)?;
-                        }
-                    }
+            findings.extend(self.check_item(key, item, access).await?);
+        }
+        Ok(findings)
+    }
 
-                    let mut out_cr_list = Vec::new();
-                    for crbinfo in &access.crbinfo_list {
-                        if Self::check_cr(&item.rules, crbinfo).await? {
-                            let crb = &crbinfo.cluster_role_binding_info;
-                            match &crb.subjects {
-                                Some(subjects) => {
-                                    let (subject, sub_kind) = {
-                                        let sub_kind_string = subjects[0].kind.to_string();
-                                        let sub_kind = match sub_kind_string.as_str() {
-                                            "User" => SubKind::User,
-                                            "Group" => SubKind::Group,
-                                            "ServiceAccount" => SubKind::ServiceAccount,
-                                            _ => {
-                                                return Err(anyhow::anyhow!(
-                                                    "Unknown subject kind: {}",
-                                                    sub_kind_string
-                                                ))
-                                            }
-                                        };
-                                        (subjects[0].name.clone(), sub_kind)
-                                    };
-                                    let out_cr = OutCR {
-                                        crb: crb.name_any(),
-                                        r: crb.role_ref.name.clone(),
-                                        age: crb.metadata.clone(),
-                                        subject,
-                                        sub_kind,
-                                    };
-                                    out_cr_list.push(out_cr);
-                                }
-                                None => {
-                                    info!(
-                                        "ClusterRoleBinding: \"{}\" does not display without subjects",
-                                        crb.name_any()
-                                    );
-                                }
-                            };
-                        }
-                    }
-                    match out_cr_list.len() {
-                        0 => {}
-                        _ => {
-                            output_cr(out_cr_list)?;
-                        }
-                    }
-                }
-                Kind::Role => {
-                    let mut out_r_list = Vec::new();
-                    for rbinfo in &access.rbinfo_list {
-                        if Self::check_r(&item.rules, rbinfo).await? {
-                            let rb = &rbinfo.role_binding_info;
-                            match &rb.subjects {
-                                Some(subjects) => {
-                                    let (subject, sub_kind) = {
-                                        let sub_kind_string = subjects[0].kind.to_string();
-                                        let sub_kind = match sub_kind_string.as_str() {
-                                            "User" => SubKind::User,
-                                            "Group" => SubKind::Group,
-                                            "ServiceAccount" => SubKind::ServiceAccount,
-                                            _ => {
-                                                return Err(anyhow::anyhow!(
-                                                    "Unknown subject kind: {}",
-                                                    sub_kind_string
-                                                ))
-                                            }
-                                        };
-                                        (subjects[0].name.clone(), sub_kind)
-                                    };
-                                    let out_r = OutR {
-                                        rb: rb.name_any(),
-                                        ns: rb.namespace().unwrap_or("default".to_string()),
-                                        r: rb.role_ref.name.clone(),
-                                        age: rb.metadata.clone(),
-                                        subject,
-                                        sub_kind,
-                                    };
-                                    out_r_list.push(out_r);
-                                }
-                                None => {
-                                    info!(
-                                        "RoleBinding: \"{}\" does not display without subjects",
-                                        rb.name_any()
-                                    );
-                                }
-                            };
-                        }
-                    }
-                    match out_r_list.len() {
-                        0 => {}
-                        _ => {
-                            output_r(out_r_list)?;
-                        }
-                    }
-                }
-                Kind::ClusterRole => {
-                    let mut out_cr_list = Vec::new();
-                    for crbinfo in &access.crbinfo_list {
-                        if Self::check_cr(&item.rules, crbinfo).await? {
-                            let crb = &crbinfo.cluster_role_binding_info;
-                            match &crb.subjects {
-                                Some(subjects) => {
-                                    let (subject, sub_kind) = {
-                                        let sub_kind_string = subjects[0].kind.to_string();
-                                        let sub_kind = match sub_kind_string.as_str() {
-                                            "User" => SubKind::User,
-                                            "Group" => SubKind::Group,
-                                            "ServiceAccount" => SubKind::ServiceAccount,
-                                            _ => {
-                                                return Err(anyhow::anyhow!(
-                                                    "Unknown subject kind: {}",
-                                                    sub_kind_string
-                                                ))
-                                            }
-                                        };
-                                        (subjects[0].name.clone(), sub_kind)
-                                    };
-                                    let out_cr = OutCR {
-                                        crb: crb.name_any(),
-                                        r: crb.role_ref.name.clone(),
-                                        age: crb.metadata.clone(),
-                                        subject,
-                                        sub_kind,
-                                    };
-                                    out_cr_list.push(out_cr);
-                                }
-                                None => {
-                                    info!(
-                                        "ClusterRoleBinding: \"{}\" does not display without subjects",
-                                        crb.name_any()
-                                    );
-                                }
-                            }
-                        }
-                    }
-                    match out_cr_list.len() {
-                        0 => {}
-                        _ => {
-                            output_cr(out_cr_list)?;
-                        }
-                    }
-                }
+    /// Run every check, handing each finding to `reporter` as soon as its check
+    /// is done. The findings are returned as well.
+    pub async fn report(
+        &self,
+        access: &Access,
+        target: &str,
+        reporter: &mut dyn Reporter,
+    ) -> Result<Vec<Finding>> {
+        let info = ScanInfo {
+            check: self,
+            target,
+            started: Utc::now(),
+        };
+        reporter.begin(&info)?;
+        let mut findings = Vec::new();
+        for (key, item) in &self.itemlist {
+            for finding in self.check_item(key, item, access).await? {
+                reporter.finding(&finding)?;
+                findings.push(finding);
             }
         }
-        Ok(())
+        reporter.end(&info)?;
+        Ok(findings)
     }
 
-    pub async fn check_r(rules: &Option<Vec<Rules>>, rbinfo: &RBInfo) -> Result<bool> {
-        let mut checked: bool = false;
-        match rules {
-            Some(rules) => {
-                for rules in rules {
-                    if let Some(resources) = &rules.resources {
-                        for resource in resources {
-                            if let Some(ref role_resources) = rbinfo.role_resources {
-                                if !role_resources.contains(&resource) {
-                                    return Ok(checked);
-                                } else {
-                                    for verb in &rules.verbs {
-                                        if !rbinfo.role_verbs.contains(&verb) {
-                                            return Ok(checked);
-                                        } else {
-                                            checked = true
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                    }
+    async fn check_item(
+        &self,
+        key: &str,
+        item: &CheckItem,
+        access: &Access,
+    ) -> Result<Vec<Finding>> {
+        info!("Start checking: {}", key);
+        let mut findings = Vec::new();
+        if let Kind::All | Kind::Role = item.kind {
+            for rbinfo in &access.rbinfo_list {
+                if let Some(rules) = Self::matched_rules(&item.rules, &rbinfo.role_rules) {
+                    findings.extend(Finding::from_rbinfo(item, rbinfo, rules));
                 }
             }
-            None => {
-                return Ok(checked);
+        }
+        if let Kind::All | Kind::ClusterRole = item.kind {
+            for crbinfo in &access.crbinfo_list {
+                if let Some(rules) = Self::matched_rules(&item.rules, &crbinfo.cluster_role_rules) {
+                    findings.extend(Finding::from_crbinfo(item, crbinfo, rules));
+                }
             }
         }
-        Ok(checked)
+        Ok(findings
+            .into_iter()
+            .flat_map(Finding::split_system)
+            .flat_map(|finding| self.exceptions.apply(finding))
+            .filter(|finding| self.show_suppressed || finding.suppressed.is_none())
+            .map(|mut finding| {
+                finding.attach_workloads(&access.workloads);
+                finding
+            })
+            .collect())
+    }
+
+    pub async fn check_r(rules: &Option<Vec<Rules>>, rbinfo: &RBInfo) -> Result<bool> {
+        Ok(Self::check_rules(rules, &rbinfo.role_rules))
     }
 
     pub async fn check_cr(rules: &Option<Vec<Rules>>, crbinfo: &CRBInfo) -> Result<bool> {
-        let mut checked: bool = false;
-        match rules {
-            Some(rules) => {
-                for rules in rules {
-                    if let Some(resources) = &rules.resources {
-                        for resource in resources {
-                            if let Some(ref clusterrole_resources) = crbinfo.cluster_role_resources
-                            {
-                                if !clusterrole_resources.contains(&resource) {
-                                    return Ok(checked);
-                                } else {
-                                    for verb in &rules.verbs {
-                                        if !crbinfo.cluster_role_verbs.contains(&verb) {
-                                            return Ok(checked);
-                                        } else {
-                                            checked = true
-                                        }
-                                    }
-                                }
-                            }
-                        }
+        Ok(Self::check_rules(rules, &crbinfo.cluster_role_rules))
+    }
+
+    fn check_rules(rules: &Option<Vec<Rules>>, granted: &[RuleInfo]) -> bool {
+        Self::matched_rules(rules, granted).is_some()
+    }
+
+    /// Every verb of every resource asked for by the check has to be granted by
+    /// at least one single rule of the role, rules are never merged together.
+    /// Returns the granted rules involved, `None` when the check doesn't match.
+    pub(crate) fn matched_rules(
+        rules: &Option<Vec<Rules>>,
+        granted: &[RuleInfo],
+    ) -> Option<Vec<RuleInfo>> {
+        let mut matched: Vec<RuleInfo> = Vec::new();
+        for rules in rules.iter().flatten() {
+            for resource in rules.resources.iter().flatten() {
+                for verb in &rules.verbs {
+                    let allowing = |name: Option<&str>| {
+                        granted
+                            .iter()
+                            .filter(|rule| rule_allows(rule, rules, resource, verb, name))
+                            .collect::<Vec<_>>()
+                    };
+                    let allowed = match &rules.resource_names {
+                        ResourceNames::Names(names) if !names.is_empty() => names
+                            .iter()
+                            .all(|name| require(&mut matched, allowing(Some(name)))),
+                        _ => require(&mut matched, allowing(None)),
+                    };
+                    if !allowed {
+                        return None;
+                    }
+                }
+            }
+            for url in rules.non_resource_urls.iter().flatten() {
+                for verb in &rules.verbs {
+                    let allowing = granted
+                        .iter()
+                        .filter(|rule| {
+                            url_matches(&rule.non_resource_urls, url)
+                                && verb_matches(&rule.verbs, verb)
+                        })
+                        .collect();
+                    if !require(&mut matched, allowing) {
+                        return None;
                     }
                 }
             }
-            None => return Ok(checked),
         }
+        match matched.len() {
+            0 => None,
+            _ => Some(matched),
+        }
+    }
+}
+
+/// Record the rules granting one requirement of a check, false when there are none.
+fn require(matched: &mut Vec<RuleInfo>, allowing: Vec<&RuleInfo>) -> bool {
+    for rule in &allowing {
+        if !matched.contains(rule) {
+            matched.push((*rule).clone());
+        }
+    }
+    !allowing.is_empty()
+}
+
+/// Wildcard used by Kubernetes for verbs, resources, apiGroups and nonResourceURLs.
+const ALL: &str = "*";
+
+fn rule_allows(
+    rule: &RuleInfo,
+    rules: &Rules,
+    resource: &str,
+    verb: &str,
+    name: Option<&str>,
+) -> bool {
+    api_group_matches(&rule.api_groups, &rules.api_groups)
+        && resource_matches(&rule.resources, resource)
+        && verb_matches(&rule.verbs, verb)
+        && name_matches(&rule.resource_names, &rules.resource_names, name)
+}
 
-        Ok(checked)
+/// `*` grants every path and a trailing `*` grants every path with that prefix.
+fn url_matches(urls: &[String], url: &str) -> bool {
+    urls.iter().any(|u| {
+        u == ALL
+            || u == url
+            || matches!(u.strip_suffix('*'), Some(prefix) if url.starts_with(prefix))
+    })
+}
+
+/// A rule without `resourceNames` covers every object, otherwise only the
+/// listed ones.
+fn name_matches(resource_names: &[String], wanted: &ResourceNames, name: Option<&str>) -> bool {
+    if resource_names.is_empty() {
+        return true;
+    }
+    match (wanted, name) {
+        (ResourceNames::Scope(NameScope::Any), _) => true,
+        (ResourceNames::Names(_), Some(name)) => resource_names.iter().any(|n| n == name),
+        _ => false,
+    }
+}
+
+fn api_group_matches(api_groups: &[String], wanted: &Option<Vec<String>>) -> bool {
+    match wanted {
+        Some(wanted) => api_groups.iter().any(|g| g == ALL || wanted.contains(g)),
+        None => true,
     }
 }
 
+fn verb_matches(verbs: &[String], verb: &str) -> bool {
+    verbs.iter().any(|v| v == ALL || v == verb)
+}
+
+/// Resources follow the API server: `*` grants everything and `*/scale` grants
+/// that subresource of any resource. `pods/*` is not a wildcard there and
+/// grants nothing but a resource literally named so.
+fn resource_matches(resources: &[String], resource: &str) -> bool {
+    let subresource = resource.split_once('/');
+    resources.iter().any(|r| {
+        if r == ALL || r == resource {
+            return true;
+        }
+        match (subresource, r.split_once('/')) {
+            (Some((_, sub)), Some((ALL, rule_sub))) => rule_sub == sub,
+            _ => false,
+        }
+    })
+}
+
 impl CheckItem {
     pub fn new(kind: Kind, rules: Option<Vec<Rules>>) -> Self {
         Self {
+            id: String::new(),
+            title: String::new(),
             // namespace: namespace,
             kind,
+            severity: Severity::Medium,
+            description: String::new(),
+            remediation: String::new(),
+            references: Vec::new(),
             rules,
         }
     }
 }
 
+impl Severity {
+    /// Checks without a `severity` are `medium`.
+    fn medium() -> Self {
+        Self::Medium
+    }
+
+    /// What a check of this severity adds to the risk score of a subject.
+    /// Each step up weighs well over the one below, so one critical check
+    /// outranks a couple of high ones.
+    pub fn weight(self) -> u32 {
+        match self {
+            Self::Info => 0,
+            Self::Low => 1,
+            Self::Medium => 3,
+            Self::High => 7,
+            Self::Critical => 15,
+        }
+    }
+}
+
+impl FromStr for Severity {
+    type Err = anyhow::Error;
+
+    fn from_str(severity: &str) -> Result<Self> {
+        match severity {
+            "info" => Ok(Self::Info),
+            "low" => Ok(Self::Low),
+            "medium" => Ok(Self::Medium),
+            "high" => Ok(Self::High),
+            "critical" => Ok(Self::Critical),
+            _ => Err(anyhow::anyhow!("Unknown severity: {}", severity)),
+        }
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self {
+            Self::Info => "info",
+            Self::Low => "low",
+            Self::Medium => "medium",
+            Self::High => "high",
+            Self::Critical => "critical",
+        };
+        f.pad(severity)
+    }
+}
+
+impl Default for ResourceNames {
+    fn default() -> Self {
+        Self::Scope(NameScope::Unrestricted)
+    }
+}
+
 impl Rules {
     pub fn new(resources: Option<Vec<String>>, verbs: Vec<String>) -> Self {
-        Self { resources, verbs }
+        Self {
+            api_groups: None,
+            resources,
+            verbs,
+            resource_names: ResourceNames::default(),
+            non_resource_urls: None,
+        }
     }
 }
 
@@ -367,3 +443,278 @@ impl Rules {
 //         }
 //     }
 // }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(resources: &[&str], verbs: &[&str]) -> RuleInfo {
+        RuleInfo {
+            resources: resources.iter().map(|r| r.to_string()).collect(),
+            verbs: verbs.iter().map(|v| v.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    fn check(resources: &[&str], verbs: &[&str]) -> Option<Vec<Rules>> {
+        Some(vec![Rules::new(
+            Some(resources.iter().map(|r| r.to_string()).collect()),
+            verbs.iter().map(|v| v.to_string()).collect(),
+        )])
+    }
+
+    #[test]
+    fn test_check_rules_per_rule() {
+        let granted = vec![rule(&["pods"], &["get"]), rule(&["secrets"], &["create"])];
+
+        assert!(Check::check_rules(&check(&["pods"], &["get"]), &granted));
+        assert!(!Check::check_rules(
+            &check(&["pods"], &["create"]),
+            &granted
+        ));
+        assert!(!Check::check_rules(
+            &check(&["secrets"], &["get"]),
+            &granted
+        ));
+    }
+
+    #[test]
+    fn test_check_rules_union_per_resource() {
+        let granted = vec![rule(&["secrets"], &["get"]), rule(&["secrets"], &["list"])];
+
+        assert!(Check::check_rules(
+            &check(&["secrets"], &["get", "list"]),
+            &granted
+        ));
+    }
+
+    #[test]
+    fn test_check_rules_wildcard() {
+        let cluster_admin = vec![rule(&["*"], &["*"])];
+        assert!(Check::check_rules(
+            &check(&["secrets"], &["get", "list"]),
+            &cluster_admin
+        ));
+        assert!(Check::check_rules(
+            &check(&["nodes/proxy"], &["create"]),
+            &cluster_admin
+        ));
+
+        let scale = vec![rule(&["*/scale"], &["update"])];
+        assert!(Check::check_rules(
+            &check(&["deployments/scale"], &["update"]),
+            &scale
+        ));
+        assert!(!Check::check_rules(
+            &check(&["deployments"], &["update"]),
+            &scale
+        ));
+
+        let pods = vec![rule(&["pods/*"], &["create"])];
+        assert!(!Check::check_rules(
+            &check(&["pods/exec"], &["create"]),
+            &pods
+        ));
+        assert!(!Check::check_rules(&check(&["pods"], &["create"]), &pods));
+        assert!(!Check::check_rules(
+            &check(&["nodes/proxy"], &["create"]),
+            &pods
+        ));
+    }
+
+    #[test]
+    fn test_check_rules_api_groups() {
+        let mut apps = rule(&["deployments"], &["create"]);
+        apps.api_groups = vec!["apps".to_string()];
+        let mut crd = rule(&["deployments"], &["create"]);
+        crd.api_groups = vec!["example.com".to_string()];
+
+        let mut rules = check(&["deployments"], &["create"]);
+        rules.as_mut().unwrap()[0].api_groups = Some(vec!["apps".to_string()]);
+
+        assert!(Check::check_rules(&rules, &[apps]));
+        assert!(!Check::check_rules(&rules, &[crd.clone()]));
+        assert!(Check::check_rules(
+            &check(&["deployments"], &["create"]),
+            &[crd]
+        ));
+    }
+
+    #[test]
+    fn test_check_rules_resource_names() {
+        let mut scoped = rule(&["secrets"], &["get"]);
+        scoped.resource_names = vec!["my-app-config".to_string()];
+        let granted = vec![scoped];
+
+        let mut rules = check(&["secrets"], &["get"]);
+        assert!(!Check::check_rules(&rules, &granted));
+
+        rules.as_mut().unwrap()[0].resource_names = ResourceNames::Scope(NameScope::Any);
+        assert!(Check::check_rules(&rules, &granted));
+
+        rules.as_mut().unwrap()[0].resource_names =
+            ResourceNames::Names(vec!["my-app-config".to_string()]);
+        assert!(Check::check_rules(&rules, &granted));
+
+        rules.as_mut().unwrap()[0].resource_names =
+            ResourceNames::Names(vec!["kube-root-ca".to_string()]);
+        assert!(!Check::check_rules(&rules, &granted));
+        assert!(Check::check_rules(&rules, &[rule(&["secrets"], &["get"])]));
+    }
+
+    #[test]
+    fn test_rules_resource_names_yaml() {
+        let rules: Rules = serde_yaml::from_str("resources: [secrets]\nverbs: [get]").unwrap();
+        assert_eq!(rules.resource_names, ResourceNames::default());
+
+        let rules: Rules =
+            serde_yaml::from_str("resources: [secrets]\nverbs: [get]\nresourceNames: any").unwrap();
+        assert_eq!(rules.resource_names, ResourceNames::Scope(NameScope::Any));
+
+        let rules: Rules =
+            serde_yaml::from_str("resources: [secrets]\nverbs: [get]\nresourceNames: [a]").unwrap();
+        assert_eq!(
+            rules.resource_names,
+            ResourceNames::Names(vec!["a".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_check_rules_non_resource_urls() {
+        let granted = vec![RuleInfo {
+            non_resource_urls: vec!["/debug/*".to_string()],
+            verbs: vec!["get".to_string()],
+            ..Default::default()
+        }];
+        let mut rules = check(&[], &["get"]);
+        let urls = |urls: &[&str]| Some(urls.iter().map(|u| u.to_string()).collect());
+
+        rules.as_mut().unwrap()[0].non_resource_urls = urls(&["/debug/pprof"]);
+        assert!(Check::check_rules(&rules, &granted));
+
+        rules.as_mut().unwrap()[0].non_resource_urls = urls(&["/metrics"]);
+        assert!(!Check::check_rules(&rules, &granted));
+
+        rules.as_mut().unwrap()[0].non_resource_urls = urls(&["*"]);
+        assert!(!Check::check_rules(&rules, &granted));
+    }
+
+    #[test]
+    fn test_fixture_non_resource_urls() {
+        let check = Check::from_yaml(include_str!("../fixtures/check.yaml")).unwrap();
+        let grant = |url: &str| {
+            vec![RuleInfo {
+                non_resource_urls: vec![url.to_string()],
+                verbs: vec!["get".to_string()],
+                ..Default::default()
+            }]
+        };
+        let pprof = &check.get_check_item("RBACR-014").unwrap().rules;
+        assert!(Check::check_rules(pprof, &grant("/debug/pprof/*")));
+        assert!(Check::check_rules(pprof, &grant("/debug/*")));
+        assert!(!Check::check_rules(pprof, &grant("/metrics")));
+
+        let wildcard = &check.get_check_item("RBACR-015").unwrap().rules;
+        assert!(Check::check_rules(wildcard, &grant("*")));
+        assert!(Check::check_rules(wildcard, &grant("/*")));
+        assert!(!Check::check_rules(wildcard, &grant("/debug/*")));
+    }
+
+    #[test]
+    fn test_severity_yaml() {
+        let check = Check::from_yaml(
+            r#"
+itemlist:
+  Bind verbs:
+    kind: All
+    severity: critical
+    rules:
+      - resources: ["rolebindings"]
+        verbs: ["bind"]
+  List Secret:
+    kind: All
+    rules:
+      - resources: ["secrets"]
+        verbs: ["list"]
+"#,
+        )
+        .unwrap();
+
+        let bind = check.get_check_item("Bind verbs").unwrap().severity;
+        let secret = check.get_check_item("List Secret").unwrap().severity;
+        assert_eq!(bind, Severity::Critical);
+        assert_eq!(secret, Severity::Medium);
+        assert!(bind >= "high".parse().unwrap());
+        assert!(secret < "high".parse().unwrap());
+    }
+
+    #[test]
+    fn test_catalogue() {
+        let check = Check::from_yaml(include_str!("../fixtures/check.yaml")).unwrap();
+
+        let ids = check.get_check().unwrap();
+        let expected: Vec<String> = (1..=ids.len()).map(|n| format!("RBACR-{n:03}")).collect();
+        assert_eq!(ids, expected);
+        for item in check.itemlist.values() {
+            assert!(!item.title.is_empty() && !item.description.is_empty());
+            assert!(!item.remediation.is_empty() && !item.references.is_empty());
+        }
+
+        let check = Check::from_yaml("itemlist:\n  Custom check:\n    kind: All\n").unwrap();
+        let item = check.get_check_item("Custom check").unwrap();
+        assert_eq!(
+            (item.id.as_str(), item.title.as_str()),
+            ("Custom check", "Custom check")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_exclude_system_keeps_broad_groups() {
+        let check = Check::from_yaml(include_str!("../fixtures/check.yaml")).unwrap();
+        let mut access = Access::with_secret_reader(
+            r#"
+apiVersion: rbac.authorization.k8s.io/v1
+kind: ClusterRoleBinding
+metadata:
+  name: anonymous-secrets
+roleRef: {apiGroup: rbac.authorization.k8s.io, kind: ClusterRole, name: secret-reader}
+subjects:
+  - {kind: User, name: "system:anonymous"}
+  - {kind: Group, name: "system:masters"}
+---
+apiVersion: rbac.authorization.k8s.io/v1
+kind: ClusterRoleBinding
+metadata:
+  name: "system:secret-readers"
+roleRef: {apiGroup: rbac.authorization.k8s.io, kind: ClusterRole, name: secret-reader}
+subjects:
+  - {kind: Group, name: "system:serviceaccounts:ci"}
+  - {kind: User, name: "system:serviceaccount:ci:deployer"}
+  - {kind: User, name: "system:serviceaccount:kube-system:job-controller"}
+"#,
+            None,
+        );
+        access.exclude_system();
+
+        let findings = check.run(&access).await.unwrap();
+        let subjects: Vec<(&str, &str)> = findings
+            .iter()
+            .filter(|finding| finding.check == "RBACR-002")
+            .flat_map(|finding| {
+                finding
+                    .subjects
+                    .iter()
+                    .map(move |subject| (finding.binding.name.as_str(), subject.name.as_str()))
+            })
+            .collect();
+        assert_eq!(
+            subjects,
+            vec![
+                ("anonymous-secrets", "system:anonymous"),
+                ("system:secret-readers", "system:serviceaccounts:ci"),
+                ("system:secret-readers", "system:serviceaccount:ci:deployer"),
+            ]
+        );
+        assert!(findings.iter().all(|finding| !finding.system));
+    }
+}
@@ -0,0 +1,367 @@
+use std::collections::BTreeMap;
+
+use anyhow::{bail, Result};
+use k8s_openapi::api::rbac::v1::Subject;
+use kube::ResourceExt;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    access::RuleInfo,
+    check::{Check, CheckItem, Kind, Severity},
+    finding::{is_false, ObjectRef, Scope, SubjectInfo},
+    print::SubKind,
+    Access,
+};
+
+/// The user name a ServiceAccount authenticates as is this prefix followed
+/// by `<namespace>:<name>`.
+const SERVICE_ACCOUNT_PREFIX: &str = "system:serviceaccount:";
+
+/// A subject together with the groups it is a member of, a binding naming
+/// either grants it its role.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Identity {
+    pub subject: SubjectInfo,
+    pub groups: Vec<String>,
+}
+
+/// A rule and every binding of the identity granting it.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Grant {
+    #[serde(flatten)]
+    pub rule: RuleInfo,
+    pub bindings: Vec<ObjectRef>,
+}
+
+/// Everything an identity is granted, deduplicated across its bindings.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Permissions {
+    pub identity: Identity,
+    /// Granted by ClusterRoleBindings, in every namespace.
+    pub cluster: Vec<Grant>,
+    /// Granted by RoleBindings, keyed by namespace.
+    pub namespaces: BTreeMap<String, Vec<Grant>>,
+}
+
+/// A check matched by the merged permissions of one scope.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct EffectiveFinding {
+    pub check: String,
+    pub title: String,
+    pub severity: Severity,
+    pub scope: Scope,
+    /// The bindings granting the matched rules.
+    pub bindings: Vec<ObjectRef>,
+    pub rules: Vec<RuleInfo>,
+    /// No single binding grants enough to match the check on its own.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub combined: bool,
+}
+
+/// The permissions of an identity and the checks they match, for
+/// `--format json` and `--format yaml`.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PermissionsReport {
+    pub permissions: Permissions,
+    pub findings: Vec<EffectiveFinding>,
+}
+
+impl Identity {
+    /// `system:serviceaccount:<namespace>:<name>` is taken as a ServiceAccount
+    /// unless `kind` says otherwise, any other name as a User. ServiceAccounts
+    /// may also be written as `<namespace>/<name>`.
+    pub fn parse(name: &str, kind: Option<SubKind>, groups: &[String]) -> Result<Self> {
+        let service_account = name
+            .strip_prefix(SERVICE_ACCOUNT_PREFIX)
+            .and_then(|rest| rest.split_once(':'));
+        let (kind, name, namespace) = match (kind, service_account) {
+            (None | Some(SubKind::ServiceAccount), Some((ns, name))) => {
+                (SubKind::ServiceAccount, name, Some(ns))
+            }
+            (Some(SubKind::ServiceAccount), None) => match name.split_once('/') {
+                Some((ns, name)) => (SubKind::ServiceAccount, name, Some(ns)),
+                None => bail!("ServiceAccount {name} needs a namespace, as <namespace>/<name>"),
+            },
+            (Some(SubKind::Other(kind)), _) => bail!("Unknown subject kind: {kind}"),
+            (Some(kind), _) => (kind, name, None),
+            (None, None) => (SubKind::User, name, None),
+        };
+
+        let subject = SubjectInfo {
+            kind,
+            name: name.to_string(),
+            namespace: namespace.map(|ns| ns.to_string()),
+            api_group: None,
+        };
+        Ok(Self::from_subject(subject, groups))
+    }
+
+    /// The subject with `groups` and the groups the API server adds for every
+    /// request, such as `system:authenticated`.
+    pub fn from_subject(subject: SubjectInfo, groups: &[String]) -> Self {
+        let mut implicit = match (&subject.kind, &subject.namespace) {
+            (SubKind::ServiceAccount, Some(ns)) => vec![
+                "system:serviceaccounts".to_string(),
+                format!("system:serviceaccounts:{ns}"),
+                "system:authenticated".to_string(),
+            ],
+            (SubKind::User, _) if subject.name == "system:anonymous" => {
+                vec!["system:unauthenticated".to_string()]
+            }
+            (SubKind::Group, _) => vec![subject.name.clone()],
+            _ => vec!["system:authenticated".to_string()],
+        };
+        for group in groups {
+            if !implicit.contains(group) {
+                implicit.push(group.clone());
+            }
+        }
+        Self {
+            subject,
+            groups: implicit,
+        }
+    }
+
+    /// Whether a subject of a binding names the identity. ServiceAccount
+    /// subjects of RoleBindings default to the namespace of the binding.
+    fn is_bound(&self, subject: &Subject, namespace: Option<&str>) -> bool {
+        match subject.kind.as_str() {
+            "Group" => self.groups.contains(&subject.name),
+            "User" => match (&self.subject.kind, &self.subject.namespace) {
+                (SubKind::User, _) => subject.name == self.subject.name,
+                (SubKind::ServiceAccount, Some(ns)) => {
+                    subject.name == format!("{SERVICE_ACCOUNT_PREFIX}{ns}:{}", self.subject.name)
+                }
+                _ => false,
+            },
+            "ServiceAccount" => {
+                self.subject.kind == SubKind::ServiceAccount
+                    && subject.name == self.subject.name
+                    && subject.namespace.as_deref().or(namespace)
+                        == self.subject.namespace.as_deref()
+            }
+            _ => false,
+        }
+    }
+
+    fn is_bound_by(&self, subjects: &Option<Vec<Subject>>, namespace: Option<&str>) -> bool {
+        subjects
+            .iter()
+            .flatten()
+            .any(|subject| self.is_bound(subject, namespace))
+    }
+}
+
+impl Permissions {
+    /// Merge the rules of every binding in `access` naming the identity.
+    pub fn from_access(access: &Access, identity: Identity) -> Self {
+        let mut cluster = Vec::new();
+        for crbinfo in &access.crbinfo_list {
+            let crb = &crbinfo.cluster_role_binding_info;
+            if identity.is_bound_by(&crb.subjects, None) {
+                let binding = ObjectRef::new("ClusterRoleBinding", crb);
+                merge(&mut cluster, &crbinfo.cluster_role_rules, &binding);
+            }
+        }
+        let mut namespaces = BTreeMap::new();
+        for rbinfo in &access.rbinfo_list {
+            let rb = &rbinfo.role_binding_info;
+            let namespace = rb.namespace().unwrap_or("default".to_string());
+            if identity.is_bound_by(&rb.subjects, Some(&namespace)) {
+                let binding = ObjectRef {
+                    kind: "RoleBinding".to_string(),
+                    name: rb.name_any(),
+                    namespace: Some(namespace.clone()),
+                };
+                merge(
+                    namespaces.entry(namespace).or_default(),
+                    &rbinfo.role_rules,
+                    &binding,
+                );
+            }
+        }
+        Self {
+            identity,
+            cluster,
+            namespaces,
+        }
+    }
+
+    /// Run the checks against the merged rules rather than binding by binding.
+    /// A check matching cluster wide is not repeated for every namespace, in
+    /// a namespace the rules of its RoleBindings count together with those of
+    /// the ClusterRoleBindings. `Kind` still limits checks to one of both.
+    pub fn check(&self, check: &Check) -> Vec<EffectiveFinding> {
+        let mut findings = Vec::new();
+        for item in check.itemlist.values() {
+            if let Kind::All | Kind::ClusterRole = item.kind {
+                if let Some(finding) = evaluate(item, Scope::Cluster, self.cluster.iter().collect())
+                {
+                    findings.push(finding);
+                    continue;
+                }
+            }
+            if let Kind::All | Kind::Role = item.kind {
+                for (namespace, grants) in &self.namespaces {
+                    let mut granted: Vec<&Grant> = grants.iter().collect();
+                    if let Kind::All = item.kind {
+                        granted.extend(&self.cluster);
+                    }
+                    findings.extend(evaluate(item, Scope::Namespace(namespace.clone()), granted));
+                }
+            }
+        }
+        findings
+    }
+}
+
+/// Add the rules of `binding`, a rule granted already only gains the binding.
+fn merge(grants: &mut Vec<Grant>, rules: &[RuleInfo], binding: &ObjectRef) {
+    for rule in rules {
+        match grants.iter_mut().find(|grant| grant.rule.same_rule(rule)) {
+            Some(grant) => {
+                if !grant.bindings.contains(binding) {
+                    grant.bindings.push(binding.clone());
+                }
+            }
+            None => grants.push(Grant {
+                rule: rule.clone(),
+                bindings: vec![binding.clone()],
+            }),
+        }
+    }
+}
+
+fn evaluate(item: &CheckItem, scope: Scope, granted: Vec<&Grant>) -> Option<EffectiveFinding> {
+    let rules_of = |binding: Option<&ObjectRef>| -> Vec<RuleInfo> {
+        granted
+            .iter()
+            .filter(|grant| match binding {
+                Some(binding) => grant.bindings.contains(binding),
+                None => true,
+            })
+            .map(|grant| grant.rule.clone())
+            .collect()
+    };
+    let rules = Check::matched_rules(&item.rules, &rules_of(None))?;
+
+    let mut bindings: Vec<ObjectRef> = Vec::new();
+    for grant in granted
+        .iter()
+        .filter(|grant| rules.iter().any(|rule| rule.same_rule(&grant.rule)))
+    {
+        for binding in &grant.bindings {
+            if !bindings.contains(binding) {
+                bindings.push(binding.clone());
+            }
+        }
+    }
+    let combined = !bindings
+        .iter()
+        .any(|binding| Check::matched_rules(&item.rules, &rules_of(Some(binding))).is_some());
+
+    Some(EffectiveFinding {
+        check: item.id.clone(),
+        title: item.title.clone(),
+        severity: item.severity,
+        scope,
+        bindings,
+        rules,
+        combined,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{check::Rules, Objects};
+
+    #[test]
+    fn test_parse() {
+        let sa = Identity::parse("system:serviceaccount:ci:deployer", None, &[]).unwrap();
+        assert_eq!(sa.subject.kind, SubKind::ServiceAccount);
+        assert_eq!(sa.subject.namespace.as_deref(), Some("ci"));
+        assert!(sa.groups.contains(&"system:serviceaccounts:ci".to_string()));
+
+        let sa = Identity::parse("ci/deployer", Some(SubKind::ServiceAccount), &[]).unwrap();
+        assert_eq!(sa.subject.name, "deployer");
+        assert!(Identity::parse("deployer", Some(SubKind::ServiceAccount), &[]).is_err());
+
+        let group = Identity::parse("devs", Some(SubKind::Group), &[]).unwrap();
+        assert_eq!(group.groups, vec!["devs"]);
+    }
+
+    #[test]
+    fn test_combined() {
+        let mut objects = Objects::default();
+        objects
+            .add_manifest(
+                r#"
+apiVersion: rbac.authorization.k8s.io/v1
+kind: Role
+metadata:
+  name: pod-reader
+  namespace: ci
+rules:
+  - apiGroups: [""]
+    resources: ["pods"]
+    verbs: ["get"]
+---
+apiVersion: rbac.authorization.k8s.io/v1
+kind: ClusterRole
+metadata:
+  name: exec
+rules:
+  - apiGroups: [""]
+    resources: ["pods/exec"]
+    verbs: ["create"]
+---
+apiVersion: rbac.authorization.k8s.io/v1
+kind: RoleBinding
+metadata:
+  name: read-pods
+  namespace: ci
+roleRef: {apiGroup: rbac.authorization.k8s.io, kind: Role, name: pod-reader}
+subjects:
+  - {kind: ServiceAccount, name: deployer}
+---
+apiVersion: rbac.authorization.k8s.io/v1
+kind: ClusterRoleBinding
+metadata:
+  name: exec
+roleRef: {apiGroup: rbac.authorization.k8s.io, kind: ClusterRole, name: exec}
+subjects:
+  - {kind: Group, name: "system:serviceaccounts:ci", apiGroup: rbac.authorization.k8s.io}
+"#,
+                None,
+            )
+            .unwrap();
+        let access = Access::from_objects(objects);
+
+        let rule = |resource: &str, verb: &str| {
+            Rules::new(Some(vec![resource.to_string()]), vec![verb.to_string()])
+        };
+        let item = CheckItem::new(
+            Kind::All,
+            Some(vec![rule("pods", "get"), rule("pods/exec", "create")]),
+        );
+        let mut check = Check::new();
+        check.add("exec".to_string(), item);
+
+        let identity = Identity::parse("system:serviceaccount:ci:deployer", None, &[]).unwrap();
+        let permissions = Permissions::from_access(&access, identity);
+        assert_eq!(permissions.cluster.len(), 1);
+        assert_eq!(permissions.namespaces["ci"].len(), 1);
+
+        let findings = permissions.check(&check);
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].scope, Scope::Namespace("ci".to_string()));
+        assert_eq!(findings[0].bindings.len(), 2);
+        assert!(findings[0].combined);
+
+        let other = Identity::parse("system:serviceaccount:prod:deployer", None, &[]).unwrap();
+        let permissions = Permissions::from_access(&access, other);
+        assert!(permissions.namespaces.is_empty());
+        assert!(permissions.check(&check).is_empty());
+    }
+}
@@ -0,0 +1,640 @@
+use std::{collections::VecDeque, fmt::Write};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    access::RuleInfo,
+    check::{Check, NameScope, ResourceNames, Rules},
+    effective::{Grant, Identity, Permissions},
+    finding::{ObjectRef, SubjectInfo},
+    print::SubKind,
+    Access,
+};
+
+/// Creating any of these runs a pod as a ServiceAccount of the namespace.
+const WORKLOADS: [(&str, &str); 7] = [
+    ("", "pods"),
+    ("apps", "deployments"),
+    ("apps", "daemonsets"),
+    ("apps", "statefulsets"),
+    ("apps", "replicasets"),
+    ("batch", "jobs"),
+    ("batch", "cronjobs"),
+];
+
+const RBAC_GROUP: &str = "rbac.authorization.k8s.io";
+
+/// The subjects of every binding and who each of them can become.
+#[derive(Debug)]
+pub struct Graph {
+    subjects: Vec<SubjectInfo>,
+    /// Subjects granted every verb on every resource cluster wide.
+    admin: Vec<bool>,
+    edges: Vec<Vec<Edge>>,
+}
+
+#[derive(Debug)]
+struct Edge {
+    /// The subject taken over, `None` when the action grants cluster-admin
+    /// by itself.
+    to: Option<usize>,
+    action: String,
+    bindings: Vec<ObjectRef>,
+}
+
+/// The shortest way a subject reaches cluster-admin equivalent power.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct EscalationPath {
+    pub subject: SubjectInfo,
+    pub steps: Vec<Step>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Step {
+    /// What the subject does, e.g. `create pods in namespace ci`.
+    pub action: String,
+    /// The subject it acts as afterwards, `None` when the action makes it
+    /// cluster-admin directly.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub becomes: Option<SubjectInfo>,
+    /// The bindings granting the action.
+    pub bindings: Vec<ObjectRef>,
+}
+
+/// The paths of a scan, for `--format json` and `--format yaml`.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct EscalationReport {
+    pub paths: Vec<EscalationPath>,
+}
+
+/// The objects of a rule `allowed` matched, `names` is `None` for all of them.
+struct Allowed {
+    names: Option<Vec<String>>,
+    bindings: Vec<ObjectRef>,
+}
+
+impl Allowed {
+    fn covers(&self, name: &str) -> bool {
+        match &self.names {
+            Some(names) => names.iter().any(|n| n == name),
+            None => true,
+        }
+    }
+}
+
+impl Graph {
+    /// Group memberships only the authenticator knows about are not taken
+    /// into account, only those implied by the kind of subject.
+    pub fn from_access(access: &Access) -> Self {
+        let subjects = subjects(access);
+        let permissions: Vec<Permissions> = subjects
+            .iter()
+            .map(|subject| {
+                Permissions::from_access(access, Identity::from_subject(subject.clone(), &[]))
+            })
+            .collect();
+        let admin = permissions
+            .iter()
+            .map(|permissions| {
+                let cluster: Vec<&Grant> = permissions.cluster.iter().collect();
+                matches!(allowed(&cluster, "*", "*", "*"), Some(all) if all.names.is_none())
+            })
+            .collect();
+        let edges = permissions
+            .iter()
+            .enumerate()
+            .map(|(index, permissions)| edges(&subjects, index, permissions))
+            .collect();
+        Self {
+            subjects,
+            admin,
+            edges,
+        }
+    }
+
+    /// A breadth first search from every subject that is not cluster-admin
+    /// equivalent already, subjects without a path are left out.
+    pub fn paths(&self) -> Vec<EscalationPath> {
+        let mut paths = Vec::new();
+        for start in (0..self.subjects.len()).filter(|&start| !self.admin[start]) {
+            let mut previous: Vec<Option<(usize, &Edge)>> = vec![None; self.subjects.len()];
+            let mut visited = vec![false; self.subjects.len()];
+            visited[start] = true;
+            let mut queue = VecDeque::from([start]);
+            let mut found: Option<(usize, Option<&Edge>)> = None;
+            'search: while let Some(node) = queue.pop_front() {
+                for edge in &self.edges[node] {
+                    match edge.to {
+                        None => {
+                            found = Some((node, Some(edge)));
+                            break 'search;
+                        }
+                        Some(to) if !visited[to] => {
+                            visited[to] = true;
+                            previous[to] = Some((node, edge));
+                            if self.admin[to] {
+                                found = Some((to, None));
+                                break 'search;
+                            }
+                            queue.push_back(to);
+                        }
+                        Some(_) => {}
+                    }
+                }
+            }
+
+            let (mut node, last) = match found {
+                Some(found) => found,
+                None => continue,
+            };
+            let mut steps: Vec<Step> = last.map(|edge| self.step(edge)).into_iter().collect();
+            while let Some((from, edge)) = previous[node] {
+                steps.push(self.step(edge));
+                node = from;
+            }
+            steps.reverse();
+            paths.push(EscalationPath {
+                subject: self.subjects[start].clone(),
+                steps,
+            });
+        }
+        paths.sort_by(|a, b| {
+            a.steps
+                .len()
+                .cmp(&b.steps.len())
+                .then_with(|| a.subject.to_string().cmp(&b.subject.to_string()))
+        });
+        paths
+    }
+
+    fn step(&self, edge: &Edge) -> Step {
+        Step {
+            action: edge.action.clone(),
+            becomes: edge.to.map(|to| self.subjects[to].clone()),
+            bindings: edge.bindings.clone(),
+        }
+    }
+}
+
+/// Every subject named by a binding, ServiceAccounts of RoleBindings
+/// defaulting to the namespace of the binding.
+fn subjects(access: &Access) -> Vec<SubjectInfo> {
+    let role_bindings = access.rbinfo_list.iter().map(|rbinfo| {
+        let rb = &rbinfo.role_binding_info;
+        (&rb.subjects, rb.metadata.namespace.as_deref())
+    });
+    let cluster_role_bindings = access
+        .crbinfo_list
+        .iter()
+        .map(|crbinfo| (&crbinfo.cluster_role_binding_info.subjects, None));
+    let mut subjects: Vec<SubjectInfo> = Vec::new();
+    for (binding_subjects, namespace) in role_bindings.chain(cluster_role_bindings) {
+        for mut subject in
+            SubjectInfo::from_subjects(binding_subjects.as_deref().unwrap_or_default())
+        {
+            if subject.kind == SubKind::ServiceAccount && subject.namespace.is_none() {
+                subject.namespace = namespace.map(|ns| ns.to_string());
+            }
+            subject.api_group = None;
+            if !subjects.iter().any(|known| {
+                known.kind == subject.kind
+                    && known.name == subject.name
+                    && known.namespace == subject.namespace
+            }) {
+                subjects.push(subject);
+            }
+        }
+    }
+    subjects
+}
+
+/// The "can become" edges of one subject, the first way to reach each
+/// other subject is kept.
+fn edges(subjects: &[SubjectInfo], index: usize, permissions: &Permissions) -> Vec<Edge> {
+    let cluster: Vec<&Grant> = permissions.cluster.iter().collect();
+    let mut scopes: Vec<(Option<&str>, Vec<&Grant>)> = vec![(None, cluster.clone())];
+    for (ns, grants) in &permissions.namespaces {
+        let mut granted: Vec<&Grant> = grants.iter().collect();
+        granted.extend(&cluster);
+        scopes.push((Some(ns), granted));
+    }
+
+    let mut edges: Vec<Edge> = Vec::new();
+    let mut add = |to: Option<usize>, action: String, allowed: &Allowed| {
+        if to != Some(index) && !edges.iter().any(|edge| edge.to == to) {
+            edges.push(Edge {
+                to,
+                action,
+                bindings: allowed.bindings.clone(),
+            });
+        }
+    };
+    for (ns, granted) in &scopes {
+        let scope = match ns {
+            Some(ns) => format!("in namespace {ns}"),
+            None => "in every namespace".to_string(),
+        };
+        for (api_group, resource) in WORKLOADS {
+            if let Some(allowed) = allowed(granted, api_group, resource, "create") {
+                for (to, _) in of_kind(subjects, &SubKind::ServiceAccount, *ns) {
+                    add(Some(to), format!("create {resource} {scope}"), &allowed);
+                }
+            }
+        }
+        if let Some(allowed) = allowed(granted, "", "secrets", "list") {
+            if allowed.names.is_none() {
+                for (to, _) in of_kind(subjects, &SubKind::ServiceAccount, *ns) {
+                    add(Some(to), format!("list secrets {scope}"), &allowed);
+                }
+            }
+        }
+        if let Some(allowed) = allowed(granted, "", "serviceaccounts/token", "create") {
+            for (to, subject) in of_kind(subjects, &SubKind::ServiceAccount, *ns) {
+                if allowed.covers(&subject.name) {
+                    let action = format!("create serviceaccounts/token {scope}");
+                    add(Some(to), action, &allowed);
+                }
+            }
+        }
+        if let Some(allowed) = allowed(granted, "", "serviceaccounts", "impersonate") {
+            for (to, subject) in of_kind(subjects, &SubKind::ServiceAccount, *ns) {
+                if allowed.covers(&subject.name) {
+                    add(
+                        Some(to),
+                        format!("impersonate serviceaccounts {scope}"),
+                        &allowed,
+                    );
+                }
+            }
+        }
+        // A RoleBinding to cluster-admin or a Role made to allow everything
+        // gives the whole namespace, and with it its ServiceAccounts.
+        let bind = allowed(granted, RBAC_GROUP, "clusterroles", "bind");
+        let create = allowed(granted, RBAC_GROUP, "rolebindings", "create");
+        if let (Some(bind), Some(_)) = (&bind, &create) {
+            if bind.covers("cluster-admin") {
+                for (to, _) in of_kind(subjects, &SubKind::ServiceAccount, *ns) {
+                    let action = format!("bind clusterrole cluster-admin {scope}");
+                    add(Some(to), action, bind);
+                }
+            }
+        }
+        let escalate = allowed(granted, RBAC_GROUP, "roles", "escalate");
+        let update = allowed(granted, RBAC_GROUP, "roles", "update");
+        if let (Some(escalate), Some(_)) = (&escalate, &update) {
+            for (to, _) in of_kind(subjects, &SubKind::ServiceAccount, *ns) {
+                add(Some(to), format!("escalate roles {scope}"), escalate);
+            }
+        }
+    }
+
+    // Users and groups are not namespaced, RoleBindings can't allow to
+    // impersonate them. Neither is cluster-admin.
+    for (kind, resource) in [(SubKind::User, "users"), (SubKind::Group, "groups")] {
+        if let Some(allowed) = allowed(&cluster, "", resource, "impersonate") {
+            for (to, subject) in of_kind(subjects, &kind, None) {
+                if allowed.covers(&subject.name) {
+                    add(Some(to), format!("impersonate {resource}"), &allowed);
+                }
+            }
+        }
+    }
+    let bind = allowed(&cluster, RBAC_GROUP, "clusterroles", "bind");
+    let create = allowed(&cluster, RBAC_GROUP, "clusterrolebindings", "create");
+    if let (Some(bind), Some(_)) = (&bind, &create) {
+        if bind.covers("cluster-admin") {
+            add(None, "bind clusterrole cluster-admin".to_string(), bind);
+        }
+    }
+    let escalate = allowed(&cluster, RBAC_GROUP, "clusterroles", "escalate");
+    let update = allowed(&cluster, RBAC_GROUP, "clusterroles", "update");
+    if let (Some(escalate), Some(_)) = (&escalate, &update) {
+        add(None, "escalate clusterroles".to_string(), escalate);
+    }
+    edges
+}
+
+/// One block per subject, a numbered line per step naming the bindings that
+/// allow it. The last step ends at cluster-admin equivalent power.
+pub fn table_paths(paths: &[EscalationPath]) -> Result<String> {
+    let mut table = String::new();
+    for path in paths {
+        let unit = match path.steps.len() {
+            1 => "step",
+            _ => "steps",
+        };
+        writeln!(table, "{} ({} {unit})", path.subject, path.steps.len())?;
+        for (number, step) in path.steps.iter().enumerate() {
+            let becomes = match &step.becomes {
+                Some(subject) if number + 1 == path.steps.len() => {
+                    format!("{subject} (cluster-admin)")
+                }
+                Some(subject) => subject.to_string(),
+                None => "cluster-admin".to_string(),
+            };
+            let bindings: Vec<String> = step.bindings.iter().map(|b| b.to_string()).collect();
+            writeln!(
+                table,
+                "  {}. {} -> {}, via {}",
+                number + 1,
+                step.action,
+                becomes,
+                bindings.join(", ")
+            )?;
+        }
+        writeln!(table)?;
+    }
+    Ok(table)
+}
+
+/// The subjects of `kind`, only those of namespace `ns` if given.
+fn of_kind<'a>(
+    subjects: &'a [SubjectInfo],
+    kind: &SubKind,
+    ns: Option<&str>,
+) -> Vec<(usize, &'a SubjectInfo)> {
+    subjects
+        .iter()
+        .enumerate()
+        .filter(|(_, subject)| {
+            &subject.kind == kind
+                && match ns {
+                    Some(ns) => subject.namespace.as_deref() == Some(ns),
+                    None => true,
+                }
+        })
+        .collect()
+}
+
+/// What the granted rules allow `verb` on, `None` when nothing.
+fn allowed(granted: &[&Grant], api_group: &str, resource: &str, verb: &str) -> Option<Allowed> {
+    let mut rules = Rules::new(Some(vec![resource.to_string()]), vec![verb.to_string()]);
+    rules.api_groups = Some(vec![api_group.to_string()]);
+    rules.resource_names = ResourceNames::Scope(NameScope::Any);
+    let candidates: Vec<RuleInfo> = granted.iter().map(|grant| grant.rule.clone()).collect();
+    let matched = Check::matched_rules(&Some(vec![rules]), &candidates)?;
+
+    let names = match matched.iter().any(|rule| rule.resource_names.is_empty()) {
+        true => None,
+        false => Some(
+            matched
+                .iter()
+                .flat_map(|rule| rule.resource_names.iter().cloned())
+                .collect(),
+        ),
+    };
+    let mut bindings: Vec<ObjectRef> = Vec::new();
+    for grant in granted
+        .iter()
+        .filter(|grant| matched.iter().any(|rule| rule.same_rule(&grant.rule)))
+    {
+        for binding in &grant.bindings {
+            if !bindings.contains(binding) {
+                bindings.push(binding.clone());
+            }
+        }
+    }
+    Some(Allowed { names, bindings })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Objects;
+
+    #[test]
+    fn test_paths() {
+        let mut objects = Objects::default();
+        objects
+            .add_manifest(
+                r#"
+apiVersion: rbac.authorization.k8s.io/v1
+kind: ClusterRole
+metadata:
+  name: cluster-admin
+rules:
+  - apiGroups: ["*"]
+    resources: ["*"]
+    verbs: ["*"]
+---
+apiVersion: rbac.authorization.k8s.io/v1
+kind: ClusterRole
+metadata:
+  name: pod-creator
+rules:
+  - apiGroups: [""]
+    resources: ["pods"]
+    verbs: ["create"]
+---
+apiVersion: rbac.authorization.k8s.io/v1
+kind: ClusterRole
+metadata:
+  name: secret-reader
+rules:
+  - apiGroups: [""]
+    resources: ["secrets"]
+    verbs: ["get", "list"]
+---
+apiVersion: rbac.authorization.k8s.io/v1
+kind: ClusterRole
+metadata:
+  name: impersonator
+rules:
+  - apiGroups: [""]
+    resources: ["groups"]
+    verbs: ["impersonate"]
+    resourceNames: ["system:masters"]
+---
+apiVersion: rbac.authorization.k8s.io/v1
+kind: RoleBinding
+metadata:
+  name: alice-pods
+  namespace: ci
+roleRef: {apiGroup: rbac.authorization.k8s.io, kind: ClusterRole, name: pod-creator}
+subjects:
+  - {kind: User, name: alice, apiGroup: rbac.authorization.k8s.io}
+---
+apiVersion: rbac.authorization.k8s.io/v1
+kind: RoleBinding
+metadata:
+  name: deployer-secrets
+  namespace: kube-system
+roleRef: {apiGroup: rbac.authorization.k8s.io, kind: ClusterRole, name: secret-reader}
+subjects:
+  - {kind: ServiceAccount, name: deployer, namespace: ci}
+---
+apiVersion: rbac.authorization.k8s.io/v1
+kind: ClusterRoleBinding
+metadata:
+  name: admin-sa
+roleRef: {apiGroup: rbac.authorization.k8s.io, kind: ClusterRole, name: cluster-admin}
+subjects:
+  - {kind: ServiceAccount, name: admin, namespace: kube-system}
+  - {kind: Group, name: "system:masters", apiGroup: rbac.authorization.k8s.io}
+---
+apiVersion: rbac.authorization.k8s.io/v1
+kind: ClusterRoleBinding
+metadata:
+  name: bob-impersonates
+roleRef: {apiGroup: rbac.authorization.k8s.io, kind: ClusterRole, name: impersonator}
+subjects:
+  - {kind: User, name: bob, apiGroup: rbac.authorization.k8s.io}
+"#,
+                None,
+            )
+            .unwrap();
+        let graph = Graph::from_access(&Access::from_objects(objects));
+        let paths: Vec<(String, Vec<String>)> = graph
+            .paths()
+            .iter()
+            .map(|path| {
+                let steps = path
+                    .steps
+                    .iter()
+                    .map(|step| format!("{} -> {}", step.action, step.becomes.as_ref().unwrap()))
+                    .collect();
+                (path.subject.to_string(), steps)
+            })
+            .collect();
+        assert_eq!(
+            paths,
+            vec![
+                (
+                    "ServiceAccount ci/deployer".to_string(),
+                    vec![
+                        "list secrets in namespace kube-system -> ServiceAccount kube-system/admin"
+                            .to_string()
+                    ]
+                ),
+                (
+                    "User bob".to_string(),
+                    vec!["impersonate groups -> Group system:masters".to_string()]
+                ),
+                (
+                    "User alice".to_string(),
+                    vec![
+                        "create pods in namespace ci -> ServiceAccount ci/deployer".to_string(),
+                        "list secrets in namespace kube-system -> ServiceAccount kube-system/admin"
+                            .to_string(),
+                    ]
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_namespaced_paths() {
+        let mut objects = Objects::default();
+        objects
+            .add_manifest(
+                r#"
+apiVersion: rbac.authorization.k8s.io/v1
+kind: ClusterRole
+metadata:
+  name: cluster-admin
+rules:
+  - apiGroups: ["*"]
+    resources: ["*"]
+    verbs: ["*"]
+---
+apiVersion: rbac.authorization.k8s.io/v1
+kind: ClusterRole
+metadata:
+  name: sa-impersonator
+rules:
+  - apiGroups: [""]
+    resources: ["serviceaccounts"]
+    verbs: ["impersonate"]
+---
+apiVersion: rbac.authorization.k8s.io/v1
+kind: ClusterRole
+metadata:
+  name: binder
+rules:
+  - apiGroups: ["rbac.authorization.k8s.io"]
+    resources: ["clusterroles"]
+    verbs: ["bind"]
+    resourceNames: ["cluster-admin"]
+  - apiGroups: ["rbac.authorization.k8s.io"]
+    resources: ["rolebindings"]
+    verbs: ["create"]
+---
+apiVersion: rbac.authorization.k8s.io/v1
+kind: Role
+metadata:
+  name: role-editor
+  namespace: kube-system
+rules:
+  - apiGroups: ["rbac.authorization.k8s.io"]
+    resources: ["roles"]
+    verbs: ["escalate", "update"]
+---
+apiVersion: rbac.authorization.k8s.io/v1
+kind: ClusterRoleBinding
+metadata:
+  name: admin-sa
+roleRef: {apiGroup: rbac.authorization.k8s.io, kind: ClusterRole, name: cluster-admin}
+subjects:
+  - {kind: ServiceAccount, name: admin, namespace: kube-system}
+---
+apiVersion: rbac.authorization.k8s.io/v1
+kind: RoleBinding
+metadata:
+  name: carol-impersonates
+  namespace: kube-system
+roleRef: {apiGroup: rbac.authorization.k8s.io, kind: ClusterRole, name: sa-impersonator}
+subjects:
+  - {kind: User, name: carol, apiGroup: rbac.authorization.k8s.io}
+---
+apiVersion: rbac.authorization.k8s.io/v1
+kind: RoleBinding
+metadata:
+  name: dave-binds
+  namespace: kube-system
+roleRef: {apiGroup: rbac.authorization.k8s.io, kind: ClusterRole, name: binder}
+subjects:
+  - {kind: User, name: dave, apiGroup: rbac.authorization.k8s.io}
+---
+apiVersion: rbac.authorization.k8s.io/v1
+kind: RoleBinding
+metadata:
+  name: erin-escalates
+  namespace: kube-system
+roleRef: {apiGroup: rbac.authorization.k8s.io, kind: Role, name: role-editor}
+subjects:
+  - {kind: User, name: erin, apiGroup: rbac.authorization.k8s.io}
+"#,
+                None,
+            )
+            .unwrap();
+        let graph = Graph::from_access(&Access::from_objects(objects));
+        let paths: Vec<(String, Vec<String>)> = graph
+            .paths()
+            .iter()
+            .map(|path| {
+                let steps = path.steps.iter().map(|step| step.action.clone()).collect();
+                (path.subject.to_string(), steps)
+            })
+            .collect();
+        let path = |subject: &str, action: &str| (subject.to_string(), vec![action.to_string()]);
+        assert_eq!(
+            paths,
+            vec![
+                path(
+                    "User carol",
+                    "impersonate serviceaccounts in namespace kube-system"
+                ),
+                path(
+                    "User dave",
+                    "bind clusterrole cluster-admin in namespace kube-system"
+                ),
+                path("User erin", "escalate roles in namespace kube-system"),
+            ]
+        );
+        assert!(graph.paths().iter().all(|path| {
+            path.steps[0].becomes.as_ref().map(|s| s.to_string())
+                == Some("ServiceAccount kube-system/admin".to_string())
+        }));
+    }
+}
@@ -0,0 +1,289 @@
+use anyhow::{bail, Context, Result};
+use k8s_openapi::chrono::{NaiveDate, Utc};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+
+use crate::finding::{Finding, SubjectInfo};
+
+/// Known-good bindings that are left out of the report. Each field of an
+/// exception that is set must match, names may end in `*` to match a prefix.
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct Exceptions {
+    pub exceptions: Vec<Exception>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct Exception {
+    /// Check id, e.g. `RBACR-002`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub check: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub binding: Option<String>,
+    /// Namespace of the RoleBinding, never matches ClusterRoleBindings.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub namespace: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub role: Option<String>,
+    /// Subject name, ServiceAccounts may also be written as `namespace/name`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub subject: Option<String>,
+    /// Last day the exception applies, as `YYYY-MM-DD`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub justification: Option<String>,
+}
+
+/// Why a finding was suppressed, carried by the findings that
+/// `--show-suppressed` keeps in the report.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Suppression {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub justification: Option<String>,
+}
+
+impl Exceptions {
+    pub async fn load_yaml(path: &str) -> Result<Self> {
+        let content = fs::read_to_string(path).await?;
+        Self::from_yaml(&content).with_context(|| format!("Failed to parse {path}"))
+    }
+
+    /// Expired exceptions are dropped with a warning, so that the bindings
+    /// they covered show up again. Exceptions that would match every finding
+    /// are refused.
+    pub fn from_yaml(content: &str) -> Result<Self> {
+        let exceptions: Exceptions = serde_yaml::from_str(content)?;
+        let today = Utc::now().naive_utc().date();
+        let mut active = Vec::new();
+        for exception in exceptions.exceptions {
+            if exception.matches_everything() {
+                bail!(
+                    "Exception without check, binding, namespace, role or subject: {:?}",
+                    exception
+                );
+            }
+            if let Some(expires) = &exception.expires {
+                let date = NaiveDate::parse_from_str(expires, "%Y-%m-%d")
+                    .with_context(|| format!("Invalid expiry date: {expires}"))?;
+                if date < today {
+                    warn!("Exception expired on {}: {:?}", expires, exception);
+                    continue;
+                }
+            }
+            active.push(exception);
+        }
+        Ok(Self { exceptions: active })
+    }
+
+    /// Split the subjects of `finding` by the first exception matching them.
+    /// The subjects no exception matches stay in `finding`, each group of
+    /// suppressed subjects becomes a copy of it carrying the suppression.
+    pub fn apply(&self, mut finding: Finding) -> Vec<Finding> {
+        let mut suppressed: Vec<(usize, Finding)> = Vec::new();
+        let mut subjects = Vec::new();
+        for subject in std::mem::take(&mut finding.subjects) {
+            let index = self
+                .exceptions
+                .iter()
+                .position(|exception| exception.matches(&finding, &subject));
+            let index = match index {
+                Some(index) => index,
+                None => {
+                    subjects.push(subject);
+                    continue;
+                }
+            };
+            match suppressed.iter_mut().find(|(seen, _)| *seen == index) {
+                Some((_, copy)) => copy.subjects.push(subject),
+                None => {
+                    let mut copy = finding.clone();
+                    copy.subjects = vec![subject];
+                    copy.suppressed = Some(self.exceptions[index].suppression());
+                    suppressed.push((index, copy));
+                }
+            }
+        }
+        finding.subjects = subjects;
+
+        let mut findings = Vec::new();
+        if !finding.subjects.is_empty() {
+            findings.push(finding);
+        }
+        findings.extend(suppressed.into_iter().map(|(_, copy)| copy));
+        findings
+    }
+}
+
+impl Exception {
+    /// A bare `*` narrows nothing down, it counts as unset.
+    fn matches_everything(&self) -> bool {
+        [
+            &self.check,
+            &self.binding,
+            &self.namespace,
+            &self.role,
+            &self.subject,
+        ]
+        .iter()
+        .all(|pattern| matches!(pattern.as_deref(), None | Some("*")))
+    }
+
+    fn matches(&self, finding: &Finding, subject: &SubjectInfo) -> bool {
+        let qualified = match &subject.namespace {
+            Some(ns) => format!("{}/{}", ns, subject.name),
+            None => subject.name.clone(),
+        };
+        let subject_matches = optional_matches(&self.subject, &subject.name)
+            || optional_matches(&self.subject, &qualified);
+        let namespace_matches = match (&self.namespace, &finding.binding.namespace) {
+            (Some(pattern), Some(ns)) => name_matches(pattern, ns),
+            (Some(_), None) => false,
+            (None, _) => true,
+        };
+        optional_matches(&self.check, &finding.check)
+            && optional_matches(&self.binding, &finding.binding.name)
+            && optional_matches(&self.role, &finding.role.name)
+            && namespace_matches
+            && subject_matches
+    }
+
+    fn suppression(&self) -> Suppression {
+        Suppression {
+            expires: self.expires.clone(),
+            justification: self.justification.clone(),
+        }
+    }
+}
+
+fn optional_matches(pattern: &Option<String>, name: &str) -> bool {
+    match pattern {
+        Some(pattern) => name_matches(pattern, name),
+        None => true,
+    }
+}
+
+fn name_matches(pattern: &str, name: &str) -> bool {
+    match pattern.strip_suffix('*') {
+        Some(prefix) => name.starts_with(prefix),
+        None => pattern == name,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{check::Severity, finding::ObjectRef, finding::Scope, SubKind};
+
+    fn subject(kind: SubKind, name: &str, namespace: Option<&str>) -> SubjectInfo {
+        SubjectInfo {
+            kind,
+            name: name.to_string(),
+            namespace: namespace.map(|ns| ns.to_string()),
+            api_group: None,
+        }
+    }
+
+    fn finding() -> Finding {
+        Finding {
+            check: "RBACR-002".to_string(),
+            title: "List secrets".to_string(),
+            severity: Severity::High,
+            binding: ObjectRef {
+                kind: "RoleBinding".to_string(),
+                name: "system:controller:token-cleaner".to_string(),
+                namespace: Some("kube-system".to_string()),
+            },
+            role: ObjectRef {
+                kind: "Role".to_string(),
+                name: "system:controller:token-cleaner".to_string(),
+                namespace: Some("kube-system".to_string()),
+            },
+            scope: Scope::Namespace("kube-system".to_string()),
+            subjects: vec![
+                subject(
+                    SubKind::ServiceAccount,
+                    "token-cleaner",
+                    Some("kube-system"),
+                ),
+                subject(SubKind::User, "alice", None),
+                subject(SubKind::Group, "devs", None),
+            ],
+            creation_timestamp: None,
+            rules: Vec::new(),
+            source: None,
+            suppressed: None,
+            system: false,
+            workloads: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_apply() {
+        let exceptions = Exceptions::from_yaml(
+            r#"
+exceptions:
+  - check: RBACR-002
+    binding: "system:controller:*"
+    namespace: kube-system
+    subject: kube-system/token-cleaner
+    justification: Bootstrap controller
+  - subject: devs
+    expires: "2000-01-01"
+  - namespace: kube-system
+    subject: "ali*"
+    expires: "2999-12-31"
+"#,
+        )
+        .unwrap();
+        assert_eq!(exceptions.exceptions.len(), 2);
+
+        let findings = exceptions.apply(finding());
+        let subjects: Vec<Vec<&str>> = findings
+            .iter()
+            .map(|f| f.subjects.iter().map(|s| s.name.as_str()).collect())
+            .collect();
+        assert_eq!(
+            subjects,
+            vec![vec!["devs"], vec!["token-cleaner"], vec!["alice"]]
+        );
+        assert_eq!(findings[0].suppressed, None);
+        assert_eq!(
+            findings[1].suppressed.as_ref().unwrap().justification,
+            Some("Bootstrap controller".to_string())
+        );
+
+        let invalid = Exceptions::from_yaml("exceptions:\n  - subject: alice\n    expires: soon\n")
+            .unwrap_err();
+        assert!(invalid.to_string().contains("Invalid expiry date"));
+    }
+
+    #[test]
+    fn test_invalid_exceptions() {
+        let unknown = Exceptions::from_yaml("exceptions:\n  - subjects: alice\n").unwrap_err();
+        assert!(unknown.to_string().contains("unknown field `subjects`"));
+        let unknown = Exceptions::from_yaml("exception:\n  - subject: alice\n").unwrap_err();
+        assert!(unknown.to_string().contains("unknown field `exception`"));
+
+        let everything = Exceptions::from_yaml(
+            "exceptions:\n  - expires: \"2999-12-31\"\n    justification: Everything\n",
+        )
+        .unwrap_err();
+        assert!(everything
+            .to_string()
+            .starts_with("Exception without check"));
+        let everything =
+            Exceptions::from_yaml("exceptions:\n  - binding: \"*\"\n    check: \"*\"\n")
+                .unwrap_err();
+        assert!(everything
+            .to_string()
+            .starts_with("Exception without check"));
+        assert!(
+            Exceptions::from_yaml("exceptions:\n  - binding: \"*\"\n    subject: alice\n").is_ok()
+        );
+    }
+}
@@ -0,0 +1,274 @@
+use std::fmt::{self, Write};
+
+use k8s_openapi::{
+    api::rbac::v1::{RoleRef, Subject},
+    apimachinery::pkg::apis::meta::v1::Time,
+};
+use kube::ResourceExt;
+use log::info;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    access::{is_system_binding, is_system_subject, CRBInfo, RBInfo, RuleInfo, Source},
+    check::{CheckItem, Severity},
+    exception::Suppression,
+    print::SubKind,
+    workload::Workload,
+};
+
+/// A binding matched by a check, together with the rules that made it match.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Finding {
+    /// The id of the check.
+    pub check: String,
+    pub title: String,
+    pub severity: Severity,
+    pub binding: ObjectRef,
+    pub role: ObjectRef,
+    pub scope: Scope,
+    pub subjects: Vec<SubjectInfo>,
+    pub creation_timestamp: Option<Time>,
+    pub rules: Vec<RuleInfo>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<Source>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub suppressed: Option<Suppression>,
+    /// Default bootstrap RBAC or only `system:` subjects.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub system: bool,
+    /// Workloads running as the subjects, when workloads were collected.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub workloads: Vec<Workload>,
+}
+
+/// Where the permissions of a binding apply.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Scope {
+    Namespace(String),
+    Cluster,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ObjectRef {
+    pub kind: String,
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub namespace: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SubjectInfo {
+    pub kind: SubKind,
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub namespace: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_group: Option<String>,
+}
+
+impl Finding {
+    /// `None` for bindings without subjects, nobody holds their permissions.
+    pub fn from_rbinfo(item: &CheckItem, rbinfo: &RBInfo, rules: Vec<RuleInfo>) -> Option<Self> {
+        let rb = &rbinfo.role_binding_info;
+        let subjects = match &rb.subjects {
+            Some(subjects) => SubjectInfo::from_subjects(subjects),
+            None => {
+                info!(
+                    "RoleBinding: \"{}\" does not display without subjects",
+                    rb.name_any()
+                );
+                return None;
+            }
+        };
+        let namespace = rb.namespace().unwrap_or("default".to_string());
+        Some(Self {
+            check: item.id.clone(),
+            title: item.title.clone(),
+            severity: item.severity,
+            binding: ObjectRef {
+                kind: "RoleBinding".to_string(),
+                name: rb.name_any(),
+                namespace: Some(namespace.clone()),
+            },
+            role: ObjectRef::from_role_ref(&rb.role_ref, Some(namespace.clone())),
+            scope: Scope::Namespace(namespace),
+            subjects,
+            creation_timestamp: rb.metadata.creation_timestamp.clone(),
+            rules,
+            source: rbinfo.source.clone(),
+            suppressed: None,
+            system: is_system_binding(rb),
+            workloads: Vec::new(),
+        })
+    }
+
+    /// `None` for bindings without subjects, nobody holds their permissions.
+    pub fn from_crbinfo(item: &CheckItem, crbinfo: &CRBInfo, rules: Vec<RuleInfo>) -> Option<Self> {
+        let crb = &crbinfo.cluster_role_binding_info;
+        let subjects = match &crb.subjects {
+            Some(subjects) => SubjectInfo::from_subjects(subjects),
+            None => {
+                info!(
+                    "ClusterRoleBinding: \"{}\" does not display without subjects",
+                    crb.name_any()
+                );
+                return None;
+            }
+        };
+        Some(Self {
+            check: item.id.clone(),
+            title: item.title.clone(),
+            severity: item.severity,
+            binding: ObjectRef {
+                kind: "ClusterRoleBinding".to_string(),
+                name: crb.name_any(),
+                namespace: None,
+            },
+            role: ObjectRef::from_role_ref(&crb.role_ref, None),
+            scope: Scope::Cluster,
+            subjects,
+            creation_timestamp: crb.metadata.creation_timestamp.clone(),
+            rules,
+            source: crbinfo.source.clone(),
+            suppressed: None,
+            system: is_system_binding(crb),
+            workloads: Vec::new(),
+        })
+    }
+}
+
+impl Finding {
+    /// Split the `system:` subjects of an ordinary binding off into a finding
+    /// of their own, tagged as system.
+    pub fn split_system(mut self) -> Vec<Self> {
+        if self.system {
+            return vec![self];
+        }
+        let (system, subjects): (Vec<SubjectInfo>, Vec<SubjectInfo>) = self
+            .subjects
+            .into_iter()
+            .partition(|subject| is_system_subject(&subject.name));
+        self.subjects = subjects;
+        if system.is_empty() {
+            return vec![self];
+        }
+        let mut copy = self.clone();
+        copy.subjects = system;
+        copy.system = true;
+        match self.subjects.is_empty() {
+            true => vec![copy],
+            false => vec![self, copy],
+        }
+    }
+
+    /// Keep the workloads that run as one of the subjects.
+    pub fn attach_workloads(&mut self, workloads: &[Workload]) {
+        let namespace = match &self.scope {
+            Scope::Namespace(ns) => Some(ns.as_str()),
+            Scope::Cluster => None,
+        };
+        self.workloads = workloads
+            .iter()
+            .filter(|workload| {
+                self.subjects
+                    .iter()
+                    .any(|subject| workload.runs_as(subject, namespace))
+            })
+            .cloned()
+            .collect();
+    }
+
+    /// One line naming the binding, its role and who it grants them to, and
+    /// the ClusterRoles an aggregated role took the matching rules from.
+    pub fn summary(&self) -> String {
+        let subjects: Vec<String> = self.subjects.iter().map(|s| s.to_string()).collect();
+        let mut summary = format!(
+            "{} grants {} to {}",
+            self.binding,
+            self.role,
+            subjects.join(", ")
+        );
+        let mut source_roles: Vec<&str> = Vec::new();
+        for role in self
+            .rules
+            .iter()
+            .filter_map(|rule| rule.source_role.as_deref())
+        {
+            if !source_roles.contains(&role) {
+                source_roles.push(role);
+            }
+        }
+        if !source_roles.is_empty() {
+            write!(summary, " (aggregated from {})", source_roles.join(", ")).unwrap();
+        }
+        summary
+    }
+}
+
+pub(crate) fn is_false(value: &bool) -> bool {
+    !value
+}
+
+impl fmt::Display for Scope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Namespace(ns) => write!(f, "namespace {}", ns),
+            Self::Cluster => f.write_str("cluster"),
+        }
+    }
+}
+
+impl fmt::Display for ObjectRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.namespace {
+            Some(ns) => write!(f, "{} {}/{}", self.kind, ns, self.name),
+            None => write!(f, "{} {}", self.kind, self.name),
+        }
+    }
+}
+
+impl fmt::Display for SubjectInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.namespace {
+            Some(ns) => write!(f, "{} {}/{}", self.kind.as_str(), ns, self.name),
+            None => write!(f, "{} {}", self.kind.as_str(), self.name),
+        }
+    }
+}
+
+impl ObjectRef {
+    pub fn new<K: ResourceExt>(kind: &str, object: &K) -> Self {
+        Self {
+            kind: kind.to_string(),
+            name: object.name_any(),
+            namespace: object.namespace(),
+        }
+    }
+
+    /// ClusterRoles are cluster scoped even when a RoleBinding refers to them.
+    fn from_role_ref(role_ref: &RoleRef, namespace: Option<String>) -> Self {
+        Self {
+            kind: role_ref.kind.clone(),
+            name: role_ref.name.clone(),
+            namespace: match role_ref.kind.as_str() {
+                "Role" => namespace,
+                _ => None,
+            },
+        }
+    }
+}
+
+impl SubjectInfo {
+    pub fn from_subjects(subjects: &[Subject]) -> Vec<Self> {
+        subjects
+            .iter()
+            .map(|subject| Self {
+                kind: SubKind::from_kind(&subject.kind),
+                name: subject.name.clone(),
+                namespace: subject.namespace.clone(),
+                api_group: subject.api_group.clone(),
+            })
+            .collect()
+    }
+}
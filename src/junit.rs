@@ -0,0 +1,210 @@
+use std::{collections::HashSet, fmt::Write};
+
+use anyhow::Result;
+
+use crate::{
+    check::Check,
+    finding::{Finding, ObjectRef},
+    print::{table_cr, table_r},
+    report::{Reporter, ScanInfo},
+    OutCR, OutR,
+};
+
+#[derive(Default)]
+pub struct JunitReporter {
+    findings: Vec<Finding>,
+}
+
+impl Reporter for JunitReporter {
+    fn finding(&mut self, finding: &Finding) -> Result<()> {
+        self.findings.push(finding.clone());
+        Ok(())
+    }
+
+    fn end(&mut self, info: &ScanInfo) -> Result<()> {
+        print!("{}", junit_report(info.check, &self.findings)?);
+        Ok(())
+    }
+}
+
+/// A JUnit XML report with one test case per check, failing with the matching
+/// bindings as they are shown in the tables.
+pub fn junit_report(check: &Check, findings: &[Finding]) -> Result<String> {
+    let check_list = check.get_check()?;
+    // Suppressed findings are known-good, they do not fail their check.
+    let findings: Vec<&Finding> = findings
+        .iter()
+        .filter(|finding| finding.suppressed.is_none())
+        .collect();
+    let failures = check_list
+        .iter()
+        .filter(|key| findings.iter().any(|finding| &finding.check == *key))
+        .count();
+
+    let mut report = String::new();
+    writeln!(report, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(
+        report,
+        r#"<testsuites name="rbacr" tests="{}" failures="{}">"#,
+        check_list.len(),
+        failures
+    )?;
+    writeln!(
+        report,
+        r#"  <testsuite name="rbacr" tests="{}" failures="{}">"#,
+        check_list.len(),
+        failures
+    )?;
+    for key in &check_list {
+        let matched: Vec<&Finding> = findings
+            .iter()
+            .copied()
+            .filter(|f| &f.check == key)
+            .collect();
+        let name = escape(&format!("{} {}", key, check.get_check_item(key)?.title));
+        if matched.is_empty() {
+            writeln!(report, r#"    <testcase classname="rbacr" name="{name}"/>"#)?;
+            continue;
+        }
+        let out_r_list: Vec<OutR> = matched.iter().flat_map(|f| OutR::from_finding(f)).collect();
+        let out_cr_list: Vec<OutCR> = matched
+            .iter()
+            .flat_map(|f| OutCR::from_finding(f))
+            .collect();
+        let mut rows = String::new();
+        if !out_r_list.is_empty() {
+            rows.push_str(&table_r(&out_r_list)?);
+        }
+        if !out_cr_list.is_empty() {
+            rows.push_str(&table_cr(&out_cr_list)?);
+        }
+        // System subjects and exceptions split one binding into several findings.
+        let bindings: HashSet<&ObjectRef> = matched.iter().map(|f| &f.binding).collect();
+        writeln!(report, r#"    <testcase classname="rbacr" name="{name}">"#)?;
+        writeln!(
+            report,
+            r#"      <failure type="{}" message="{} binding(s) match">{}</failure>"#,
+            matched[0].severity,
+            bindings.len(),
+            escape(&rows)
+        )?;
+        writeln!(report, "    </testcase>")?;
+    }
+    writeln!(report, "  </testsuite>")?;
+    writeln!(report, "</testsuites>")?;
+    Ok(report)
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Access;
+
+    #[tokio::test]
+    async fn test_junit_report() {
+        let check = Check::from_yaml(
+            r#"
+itemlist:
+  RBACR-901:
+    title: Read "secrets" & <tokens>
+    kind: All
+    severity: high
+    rules:
+      - apiGroups: [""]
+        resources: ["secrets"]
+        verbs: ["get"]
+  RBACR-902:
+    title: Create pods
+    kind: All
+    rules:
+      - apiGroups: [""]
+        resources: ["pods"]
+        verbs: ["create"]
+  RBACR-903:
+    title: Proxy nodes
+    kind: All
+    rules:
+      - apiGroups: [""]
+        resources: ["nodes/proxy"]
+        verbs: ["get"]
+"#,
+        )
+        .unwrap();
+        let access = Access::with_secret_reader(
+            r#"
+apiVersion: rbac.authorization.k8s.io/v1
+kind: ClusterRoleBinding
+metadata:
+  name: readers
+roleRef: {apiGroup: rbac.authorization.k8s.io, kind: ClusterRole, name: secret-reader}
+subjects:
+  - {kind: User, name: "a&b"}
+  - {kind: Group, name: "<ops>"}
+  - {kind: Group, name: "system:masters"}
+---
+apiVersion: rbac.authorization.k8s.io/v1
+kind: RoleBinding
+metadata:
+  name: ci-readers
+  namespace: ci
+roleRef: {apiGroup: rbac.authorization.k8s.io, kind: ClusterRole, name: secret-reader}
+subjects:
+  - {kind: User, name: '"quoted"'}
+---
+apiVersion: rbac.authorization.k8s.io/v1
+kind: Role
+metadata:
+  name: pod-creator
+  namespace: ci
+rules:
+  - {apiGroups: [""], resources: ["pods"], verbs: ["create"]}
+---
+apiVersion: rbac.authorization.k8s.io/v1
+kind: RoleBinding
+metadata:
+  name: ci-pods
+  namespace: ci
+roleRef: {apiGroup: rbac.authorization.k8s.io, kind: Role, name: pod-creator}
+subjects:
+  - {kind: User, name: deployer}
+"#,
+            None,
+        );
+        let findings = check.run(&access).await.unwrap();
+        let report = junit_report(&check, &findings).unwrap();
+
+        assert!(report.contains(r#"<testsuites name="rbacr" tests="3" failures="2">"#));
+        assert!(report.contains(r#"<testsuite name="rbacr" tests="3" failures="2">"#));
+        let failures: Vec<&str> = report
+            .lines()
+            .filter(|line| line.contains("<failure "))
+            .map(|line| &line[..line.find('>').unwrap() + 1])
+            .collect();
+        assert_eq!(
+            failures,
+            vec![
+                r#"      <failure type="high" message="2 binding(s) match">"#,
+                r#"      <failure type="medium" message="1 binding(s) match">"#,
+            ]
+        );
+        assert!(report.contains(
+            r#"<testcase classname="rbacr" name="RBACR-901 Read &quot;secrets&quot; &amp; &lt;tokens&gt;">"#
+        ));
+        assert!(report.contains(r#"<testcase classname="rbacr" name="RBACR-903 Proxy nodes"/>"#));
+
+        for escaped in ["a&amp;b", "&lt;ops&gt;", "&quot;quoted&quot;"] {
+            assert!(report.contains(escaped), "{escaped} missing");
+        }
+        for raw in ["a&b", "<ops>", "\"quoted\""] {
+            assert!(!report.contains(raw), "{raw} not escaped");
+        }
+    }
+}
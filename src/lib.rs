@@ -1,7 +1,35 @@
 mod access;
+mod aggregate;
 mod check;
+mod effective;
+mod escalation;
+mod exception;
+mod finding;
+mod junit;
+mod manifest;
 mod print;
+mod report;
+mod sarif;
+mod subject;
+mod who_can;
+mod workload;
 
-pub use access::Access;
-pub use check::Check;
-pub use print::{output_cr, output_r, OutCR, OutR};
+pub use access::{Access, Objects, RuleInfo};
+pub use check::{Check, Severity};
+pub use effective::{EffectiveFinding, Grant, Identity, Permissions, PermissionsReport};
+pub use escalation::{table_paths, EscalationPath, EscalationReport, Graph, Step};
+pub use exception::{Exception, Exceptions, Suppression};
+pub use finding::{Finding, ObjectRef, Scope, SubjectInfo};
+pub use junit::{junit_report, JunitReporter};
+pub use print::{
+    output_cr, output_r, table_cr, table_effective, table_grants, table_r, JsonReporter, OutCR,
+    OutR, Report, SubKind, TableReporter, YamlReporter,
+};
+pub use report::{Reporter, ScanInfo};
+pub use sarif::{sarif_report, SarifReporter};
+pub use subject::{
+    subject_report, table_subjects, SubjectFinding, SubjectFormat, SubjectReport, SubjectReporter,
+    SubjectSummary,
+};
+pub use who_can::{WhoCan, WHO_CAN};
+pub use workload::Workload;
@@ -1,25 +1,374 @@
+use std::path::PathBuf;
+
 use anyhow::Result;
-use kube::Client;
-use log::info;
+use clap::{Parser, Subcommand, ValueEnum};
+use kube::{
+    config::{KubeConfigOptions, Kubeconfig},
+    Client, Config,
+};
+use log::{info, LevelFilter};
 use simple_logger::SimpleLogger;
 use time::UtcOffset;
 
-use rbacr::{Access, Check};
+use rbacr::{
+    table_effective, table_grants, table_paths, Access, Check, EscalationReport, Exceptions,
+    Finding, Graph, Identity, JsonReporter, JunitReporter, Permissions, PermissionsReport,
+    Reporter, SarifReporter, Severity, SubKind, SubjectFormat, SubjectReporter, TableReporter,
+    WhoCan, YamlReporter,
+};
+
+/// Exit code of a scan with findings at or above `--fail-on`, 1 is left to
+/// errors and 2 to usage errors.
+const EXIT_FINDINGS: i32 = 3;
+
+/// Detection tool for possible attacks on RBAC in kubernetes.
+#[derive(Parser, Debug)]
+#[command(author, version, about)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Check file to use instead of the built-in checks
+    #[arg(long, global = true)]
+    checks: Option<PathBuf>,
+
+    /// Read RBAC manifests from a file, a directory or `-` for stdin instead of a cluster
+    #[arg(short = 'f', long, global = true)]
+    manifests: Option<String>,
+
+    /// Path to the kubeconfig file
+    #[arg(long, global = true)]
+    kubeconfig: Option<PathBuf>,
+
+    /// Kubeconfig context to use
+    #[arg(long, global = true)]
+    context: Option<String>,
+
+    /// Only report RoleBindings of this namespace
+    #[arg(short, long, global = true)]
+    namespace: Option<String>,
+
+    /// Output format of the findings
+    #[arg(long, value_enum, default_value_t = Format::Table, global = true)]
+    format: Format,
+
+    /// Show the findings per check, or per subject with the checks it triggers
+    /// and a risk score, riskiest subjects first
+    #[arg(long, value_enum, default_value_t = GroupBy::Check, global = true)]
+    group_by: GroupBy,
+
+    /// Leave out default bootstrap RBAC (bindings labelled
+    /// kubernetes.io/bootstrapping=rbac-defaults) and system: subjects such as
+    /// system:masters, instead of tagging them. Bindings to system:anonymous,
+    /// system:authenticated, system:unauthenticated and system:serviceaccounts[:<ns>]
+    /// are always checked
+    #[arg(long, global = true)]
+    exclude_system: bool,
+
+    /// Also list Pods, Deployments, ReplicaSets, ReplicationControllers,
+    /// DaemonSets, StatefulSets, Jobs and CronJobs and show the workloads
+    /// running as each ServiceAccount with findings. Workloads in manifests
+    /// are only shown with this flag
+    #[arg(long, global = true)]
+    workloads: bool,
+
+    /// Exceptions file of known-good bindings to leave out of the findings
+    #[arg(long, global = true)]
+    exceptions: Option<PathBuf>,
+
+    /// Report the findings matched by the exceptions file as suppressed
+    #[arg(long, global = true)]
+    show_suppressed: bool,
+
+    /// Exit with code 3 when findings of this severity or higher exist:
+    /// info, low, medium, high or critical
+    #[arg(long, global = true)]
+    fail_on: Option<Severity>,
+
+    /// Log level: off, error, warn, info, debug or trace
+    #[arg(long, default_value_t = LevelFilter::Info, global = true)]
+    log_level: LevelFilter,
+
+    /// UTC offset of the log timestamps, e.g. +08:00
+    #[arg(long, default_value = "+00:00", value_parser = parse_utc_offset, global = true)]
+    utc_offset: UtcOffset,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Run the checks against the cluster or manifests (default)
+    Scan,
+    /// List the ids, severities and titles of the checks
+    ListChecks,
+    /// Show what a check looks for
+    Explain {
+        /// Id of the check, e.g. RBACR-001
+        check: String,
+    },
+    /// List the subjects holding a permission and the bindings granting it,
+    /// in the namespace given with -n or in any namespace
+    WhoCan {
+        /// Verb such as get, create or delete
+        verb: String,
+        /// Resource such as pods or deployments.apps, or a nonResourceURL such as /metrics
+        resource: String,
+        /// Subresource such as log or exec
+        #[arg(long)]
+        subresource: Option<String>,
+        /// API group of the resource, the core group by default
+        #[arg(long, default_value = "")]
+        api_group: String,
+        /// Name of the object, also counting rules limited to it by resourceNames
+        #[arg(long)]
+        name: Option<String>,
+    },
+    /// Merge everything bound to one subject across its bindings and run the
+    /// checks against the result, catching permissions that are only
+    /// dangerous together
+    Permissions {
+        /// User, group or ServiceAccount, e.g. system:serviceaccount:ci:deployer
+        subject: String,
+        /// Kind of the subject, a User unless it is a system:serviceaccount: name
+        #[arg(long, value_enum)]
+        kind: Option<SubjectKind>,
+        /// Further group the subject is a member of, may be repeated
+        #[arg(long = "group")]
+        groups: Vec<String>,
+    },
+    /// Show the shortest ways subjects reach cluster-admin equivalent power by
+    /// creating pods, reading secrets, requesting tokens, impersonating or
+    /// binding roles
+    EscalationPaths,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum GroupBy {
+    Check,
+    Subject,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum SubjectKind {
+    User,
+    Group,
+    ServiceAccount,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum Format {
+    Table,
+    Json,
+    Yaml,
+    Sarif,
+    Junit,
+}
 
 #[tokio::main]
 async fn main() -> Result<()> {
+    let cli = Cli::parse();
     SimpleLogger::new()
-        .with_level(log::LevelFilter::Info)
-        .with_utc_offset(UtcOffset::from_hms(8, 0, 0).unwrap())
+        .with_level(cli.log_level)
+        .with_utc_offset(cli.utc_offset)
         .init()
         .unwrap();
 
-    info!("Start rbac-rs");
-    let client = Client::try_default().await?;
-    let access = Access::run(client).await?;
-    let content = include_str!("../fixtures/check.yaml");
-    let check = Check::from_yaml(content)?;
-    check.run(access).await?;
+    let mut check = match &cli.checks {
+        Some(path) => Check::load_yaml(&path.to_string_lossy()).await?,
+        None => Check::from_yaml(include_str!("../fixtures/check.yaml"))?,
+    };
+    if let Some(path) = &cli.exceptions {
+        check.exceptions = Exceptions::load_yaml(&path.to_string_lossy()).await?;
+    }
+    check.show_suppressed = cli.show_suppressed;
+
+    match &cli.command {
+        Some(Command::Scan) | None => {
+            info!("Start rbac-rs");
+            let findings = scan(&cli, &check).await?;
+            if let Some(threshold) = cli.fail_on {
+                if findings
+                    .iter()
+                    .any(|finding| finding.suppressed.is_none() && finding.severity >= threshold)
+                {
+                    std::process::exit(EXIT_FINDINGS);
+                }
+            }
+        }
+        Some(Command::ListChecks) => {
+            for item in check.itemlist.values() {
+                println!("{:<10} {:<8} {}", item.id, item.severity, item.title);
+            }
+        }
+        Some(Command::Explain { check: key }) => {
+            let item = check.get_check_item(key)?;
+            println!("{key}:");
+            print!("{}", serde_yaml::to_string(item)?);
+        }
+        Some(Command::WhoCan {
+            verb,
+            resource,
+            subresource,
+            api_group,
+            name,
+        }) => {
+            let query = WhoCan {
+                verb: verb.clone(),
+                resource: resource.clone(),
+                subresource: subresource.clone(),
+                api_group: api_group.clone(),
+                name: name.clone(),
+            };
+            scan(&cli, &query.check()).await?;
+        }
+        Some(Command::Permissions {
+            subject,
+            kind,
+            groups,
+        }) => {
+            let kind = kind.map(|kind| match kind {
+                SubjectKind::User => SubKind::User,
+                SubjectKind::Group => SubKind::Group,
+                SubjectKind::ServiceAccount => SubKind::ServiceAccount,
+            });
+            let identity = Identity::parse(subject, kind, groups)?;
+            let access = load_access(&cli).await?;
+            let permissions = Permissions::from_access(&access, identity);
+            let findings = permissions.check(&check);
+            let failed = matches!(cli.fail_on, Some(threshold)
+                if findings.iter().any(|finding| finding.severity >= threshold));
+            print_permissions(
+                cli.format,
+                PermissionsReport {
+                    permissions,
+                    findings,
+                },
+            )?;
+            if failed {
+                std::process::exit(EXIT_FINDINGS);
+            }
+        }
+        Some(Command::EscalationPaths) => {
+            let access = load_access(&cli).await?;
+            let paths = Graph::from_access(&access).paths();
+            print_paths(cli.format, EscalationReport { paths })?;
+        }
+    }
 
     Ok(())
 }
+
+/// Run `check` and print its findings in the chosen format.
+async fn scan(cli: &Cli, check: &Check) -> Result<Vec<Finding>> {
+    let access = load_access(cli).await?;
+    let mut reporter: Box<dyn Reporter> = match (cli.group_by, cli.format) {
+        (GroupBy::Check, Format::Table) => Box::<TableReporter>::default(),
+        (GroupBy::Check, Format::Json) => Box::<JsonReporter>::default(),
+        (GroupBy::Check, Format::Yaml) => Box::<YamlReporter>::default(),
+        (GroupBy::Check, Format::Sarif) => Box::<SarifReporter>::default(),
+        (GroupBy::Check, Format::Junit) => Box::<JunitReporter>::default(),
+        (GroupBy::Subject, Format::Table) => Box::new(SubjectReporter::new(SubjectFormat::Table)),
+        (GroupBy::Subject, Format::Json) => Box::new(SubjectReporter::new(SubjectFormat::Json)),
+        (GroupBy::Subject, Format::Yaml) => Box::new(SubjectReporter::new(SubjectFormat::Yaml)),
+        (GroupBy::Subject, Format::Sarif | Format::Junit) => {
+            anyhow::bail!("--group-by subject supports the table, json and yaml formats")
+        }
+    };
+    check.report(&access, &target(cli), reporter.as_mut()).await
+}
+
+fn print_paths(format: Format, report: EscalationReport) -> Result<()> {
+    match format {
+        Format::Table => print!("{}", table_paths(&report.paths)?),
+        Format::Json => println!("{}", serde_json::to_string_pretty(&report)?),
+        Format::Yaml => print!("{}", serde_yaml::to_string(&report)?),
+        Format::Sarif | Format::Junit => {
+            anyhow::bail!("escalation-paths supports the table, json and yaml formats")
+        }
+    }
+    Ok(())
+}
+
+fn print_permissions(format: Format, report: PermissionsReport) -> Result<()> {
+    match format {
+        Format::Table => {
+            let permissions = &report.permissions;
+            let identity = &permissions.identity;
+            println!(
+                "{} (groups: {})",
+                identity.subject,
+                identity.groups.join(", ")
+            );
+            if !permissions.cluster.is_empty() {
+                println!("\ncluster");
+                print!("{}", table_grants(&permissions.cluster)?);
+            }
+            for (ns, grants) in &permissions.namespaces {
+                println!("\nnamespace {ns}");
+                print!("{}", table_grants(grants)?);
+            }
+            if !report.findings.is_empty() {
+                println!();
+                print!("{}", table_effective(&report.findings)?);
+            }
+        }
+        Format::Json => println!("{}", serde_json::to_string_pretty(&report)?),
+        Format::Yaml => print!("{}", serde_yaml::to_string(&report)?),
+        Format::Sarif | Format::Junit => {
+            anyhow::bail!("permissions supports the table, json and yaml formats")
+        }
+    }
+    Ok(())
+}
+
+async fn load_access(cli: &Cli) -> Result<Access> {
+    let mut access = match &cli.manifests {
+        Some(path) => Access::from_manifests(path).await?,
+        None if cli.workloads => Access::run_with_workloads(client(cli).await?).await?,
+        None => Access::run(client(cli).await?).await?,
+    };
+    if !cli.workloads {
+        access.workloads.clear();
+    }
+    if let Some(ns) = &cli.namespace {
+        access.retain_namespace(ns);
+    }
+    if cli.exclude_system {
+        access.exclude_system();
+    }
+    Ok(access)
+}
+
+/// What the scan is run against, as told to the reporters.
+fn target(cli: &Cli) -> String {
+    match (&cli.manifests, &cli.context) {
+        (Some(path), _) => path.clone(),
+        (None, Some(context)) => context.clone(),
+        (None, None) => "current-context".to_string(),
+    }
+}
+
+async fn client(cli: &Cli) -> Result<Client> {
+    let options = KubeConfigOptions {
+        context: cli.context.clone(),
+        ..Default::default()
+    };
+    let config = match &cli.kubeconfig {
+        Some(path) => {
+            Config::from_custom_kubeconfig(Kubeconfig::read_from(path)?, &options).await?
+        }
+        None if cli.context.is_some() => Config::from_kubeconfig(&options).await?,
+        None => Config::infer().await?,
+    };
+    Ok(Client::try_from(config)?)
+}
+
+/// Accepts `+08:00`, `-05:30`, `8` and the like.
+fn parse_utc_offset(offset: &str) -> Result<UtcOffset> {
+    let (sign, offset) = match offset.strip_prefix('-') {
+        Some(offset) => (-1, offset),
+        None => (1, offset.trim_start_matches('+')),
+    };
+    let (hours, minutes) = offset.split_once(':').unwrap_or((offset, "0"));
+    let (hours, minutes): (i8, i8) = (hours.parse()?, minutes.parse()?);
+    Ok(UtcOffset::from_hms(sign * hours, sign * minutes, 0)?)
+}
@@ -0,0 +1,387 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use k8s_openapi::api::rbac::v1::{ClusterRole, ClusterRoleBinding, Role, RoleBinding};
+use kube::ResourceExt;
+use log::warn;
+use serde_yaml::{Mapping, Value};
+use tokio::{fs, io::AsyncReadExt};
+
+use crate::{
+    access::{Objects, Source},
+    finding::ObjectRef,
+};
+
+const RBAC_GROUP: &str = "rbac.authorization.k8s.io";
+
+impl Objects {
+    /// Read RBAC objects and workloads from a manifest file, every `.yaml`, `.yml` and `.json`
+    /// file below a directory, or stdin when `path` is `-`.
+    pub async fn load(path: &str) -> Result<Self> {
+        let mut objects = Self::default();
+        if path == "-" {
+            let mut content = String::new();
+            tokio::io::stdin().read_to_string(&mut content).await?;
+            objects.add_manifest(&content, None)?;
+            return Ok(objects);
+        }
+
+        if !fs::metadata(path).await?.is_dir() {
+            let content = fs::read_to_string(path).await?;
+            objects
+                .add_manifest(&content, Some(path))
+                .with_context(|| format!("Failed to parse {path}"))?;
+            return Ok(objects);
+        }
+        for file in manifest_files(Path::new(path)).await? {
+            let content = fs::read_to_string(&file).await?;
+            // Directories of charts and GitOps repos hold plenty of files that are
+            // not Kubernetes objects, those must not stop the scan.
+            if let Err(err) = objects.add_manifest(&content, Some(&file.to_string_lossy())) {
+                warn!("Skip {}: {}", file.display(), err);
+            }
+        }
+        Ok(objects)
+    }
+
+    /// Add the objects of a multi-document YAML or JSON stream, unwrapping
+    /// `List` and `RoleBindingList`-style documents. Objects remember their
+    /// line in `file` when it is given.
+    pub fn add_manifest(&mut self, content: &str, file: Option<&str>) -> Result<()> {
+        for mut document in Document::split(content, file) {
+            let value: Value = serde_yaml::from_str(&document.lines.join("\n"))?;
+            self.add_value(value, None, false, &mut document)?;
+        }
+        Ok(())
+    }
+
+    /// `list` holds the kind and apiVersion of the enclosing typed list, whose
+    /// items may leave them out.
+    fn add_value(
+        &mut self,
+        value: Value,
+        list: Option<(&str, &str)>,
+        in_list: bool,
+        document: &mut Document,
+    ) -> Result<()> {
+        let mut object = match value {
+            Value::Mapping(object) => object,
+            _ => return Ok(()),
+        };
+        if let Some((kind, api_version)) = list {
+            if object.get("kind").is_none() {
+                object.insert("kind".into(), kind.into());
+            }
+            if object.get("apiVersion").is_none() {
+                object.insert("apiVersion".into(), api_version.into());
+            }
+        }
+        let kind = field(&object, "kind");
+        let api_version = field(&object, "apiVersion");
+
+        if let Some(item_kind) = kind.strip_suffix("List") {
+            if let Some(Value::Sequence(items)) = object.remove("items") {
+                let list = match item_kind {
+                    "" => None,
+                    _ => Some((item_kind, api_version.as_str())),
+                };
+                for item in items {
+                    self.add_value(item, list, true, document)?;
+                }
+            }
+            return Ok(());
+        }
+
+        let group = api_version.rsplit_once('/').map_or("", |(group, _)| group);
+        match (group, kind.as_str()) {
+            ("", "Namespace") => self.namespaces.push(serde_yaml::from_value(object.into())?),
+            (RBAC_GROUP, "Role") => {
+                let mut role: Role = from_rbac(object)?;
+                role.metadata.namespace.get_or_insert("default".to_string());
+                self.add_source(&kind, &role, in_list, document);
+                self.roles.push(role);
+            }
+            (RBAC_GROUP, "RoleBinding") => {
+                let mut rb: RoleBinding = from_rbac(object)?;
+                rb.metadata.namespace.get_or_insert("default".to_string());
+                self.add_source(&kind, &rb, in_list, document);
+                self.role_bindings.push(rb);
+            }
+            (RBAC_GROUP, "ClusterRole") => {
+                let cr: ClusterRole = from_rbac(object)?;
+                self.add_source(&kind, &cr, in_list, document);
+                self.cluster_roles.push(cr);
+            }
+            ("", "Pod" | "ReplicationController")
+            | ("apps", "Deployment" | "ReplicaSet" | "DaemonSet" | "StatefulSet")
+            | ("batch", "Job" | "CronJob") => self.add_workload_value(&kind, object),
+            (RBAC_GROUP, "ClusterRoleBinding") => {
+                let crb: ClusterRoleBinding = from_rbac(object)?;
+                self.add_source(&kind, &crb, in_list, document);
+                self.cluster_role_bindings.push(crb);
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn add_source<K: ResourceExt>(
+        &mut self,
+        kind: &str,
+        object: &K,
+        in_list: bool,
+        document: &mut Document,
+    ) {
+        if let Some(file) = document.file {
+            let line = match in_list {
+                true => document.find_name(&object.name_any()),
+                false => document.first_line(),
+            };
+            self.sources.insert(
+                ObjectRef::new(kind, object),
+                Source {
+                    file: file.to_string(),
+                    line,
+                },
+            );
+        }
+    }
+}
+
+/// One document of a multi-document stream, with enough of its text kept to
+/// tell which line an object starts at.
+struct Document<'a> {
+    file: Option<&'a str>,
+    /// 1-based line of `lines[0]` in the file.
+    start: usize,
+    lines: Vec<&'a str>,
+    /// Where to look for the next item of a list.
+    cursor: usize,
+}
+
+impl<'a> Document<'a> {
+    fn split(content: &'a str, file: Option<&'a str>) -> Vec<Self> {
+        let mut documents = vec![Self::new(file, 1)];
+        for (index, line) in content.lines().enumerate() {
+            if line == "---" || line.starts_with("--- ") || line.starts_with("---\t") {
+                documents.push(Self::new(file, index + 2));
+            } else {
+                documents.last_mut().unwrap().lines.push(line);
+            }
+        }
+        documents
+    }
+
+    fn new(file: Option<&'a str>, start: usize) -> Self {
+        Self {
+            file,
+            start,
+            lines: Vec::new(),
+            cursor: 0,
+        }
+    }
+
+    /// The first line that is neither blank nor a comment.
+    fn first_line(&self) -> usize {
+        let index = self
+            .lines
+            .iter()
+            .position(|line| !line.trim().is_empty() && !line.trim_start().starts_with('#'))
+            .unwrap_or(0);
+        self.start + index
+    }
+
+    /// Items of `List` documents are located by their `metadata.name` line,
+    /// searching on from the previous item.
+    fn find_name(&mut self, name: &str) -> usize {
+        let found =
+            (self.cursor..self.lines.len()).find(|&index| match key_value(self.lines[index]) {
+                Some(("name", value)) => value == name && self.parent(index) == Some("metadata"),
+                _ => false,
+            });
+        match found {
+            Some(index) => {
+                self.cursor = index + 1;
+                self.start + index
+            }
+            None => self.first_line(),
+        }
+    }
+
+    /// The key of the closest less indented line, which holds the mapping of
+    /// the line at `index`.
+    fn parent(&self, index: usize) -> Option<&str> {
+        let indent = indentation(self.lines[index]);
+        self.lines[..index]
+            .iter()
+            .rev()
+            .find(|line| !line.trim().is_empty() && indentation(line) < indent)
+            .and_then(|line| key_value(line))
+            .map(|(key, _)| key)
+    }
+}
+
+fn indentation(line: &str) -> usize {
+    line.len() - line.trim_start().len()
+}
+
+/// Split a YAML `key: value` or JSON `"key": "value",` line, unquoted.
+fn key_value(line: &str) -> Option<(&str, &str)> {
+    let line = line.trim().trim_start_matches("- ").trim_end_matches(',');
+    let (key, value) = line.split_once(':')?;
+    Some((
+        key.trim().trim_matches('"'),
+        value.trim().trim_matches(|c| c == '"' || c == '\''),
+    ))
+}
+
+fn field(object: &Mapping, key: &str) -> String {
+    object
+        .get(key)
+        .and_then(Value::as_str)
+        .unwrap_or_default()
+        .to_string()
+}
+
+/// The deprecated v1beta1 and v1alpha1 RBAC objects share the v1 schema.
+fn from_rbac<T: serde::de::DeserializeOwned>(mut object: Mapping) -> Result<T> {
+    object.insert("apiVersion".into(), format!("{RBAC_GROUP}/v1").into());
+    Ok(serde_yaml::from_value(object.into())?)
+}
+
+async fn manifest_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    let mut dirs = vec![dir.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        let mut entries = fs::read_dir(&dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            if entry.file_type().await?.is_dir() {
+                dirs.push(path);
+            } else if matches!(
+                path.extension().and_then(|ext| ext.to_str()),
+                Some("yaml" | "yml" | "json")
+            ) {
+                files.push(path);
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_add_manifest_documents() {
+        let mut objects = Objects::default();
+        objects
+            .add_manifest(
+                r#"
+apiVersion: rbac.authorization.k8s.io/v1
+kind: Role
+metadata:
+  name: pod-reader
+rules:
+  - apiGroups: [""]
+    resources: ["pods"]
+    verbs: ["get"]
+---
+apiVersion: v1
+kind: ConfigMap
+metadata:
+  name: ignored
+---
+{"apiVersion": "rbac.authorization.k8s.io/v1beta1", "kind": "ClusterRoleBinding",
+ "metadata": {"name": "admins"},
+ "roleRef": {"apiGroup": "rbac.authorization.k8s.io", "kind": "ClusterRole", "name": "admin"}}
+"#,
+                Some("rbac.yaml"),
+            )
+            .unwrap();
+
+        assert_eq!(objects.roles.len(), 1);
+        assert_eq!(objects.roles[0].namespace(), Some("default".to_string()));
+        assert_eq!(objects.cluster_role_bindings[0].name_any(), "admins");
+        assert_eq!(objects.source("Role", &objects.roles[0]).unwrap().line, 2);
+        assert_eq!(
+            objects
+                .source("ClusterRoleBinding", &objects.cluster_role_bindings[0])
+                .unwrap()
+                .line,
+            16
+        );
+    }
+
+    #[test]
+    fn test_add_manifest_lists() {
+        let mut objects = Objects::default();
+        objects
+            .add_manifest(
+                r#"
+apiVersion: v1
+kind: List
+items:
+  - apiVersion: v1
+    kind: Namespace
+    metadata:
+      name: ci
+  - apiVersion: rbac.authorization.k8s.io/v1
+    kind: RoleBindingList
+    items:
+      - metadata:
+          name: deployer
+          namespace: ci
+        roleRef:
+          apiGroup: rbac.authorization.k8s.io
+          kind: ClusterRole
+          name: edit
+"#,
+                None,
+            )
+            .unwrap();
+
+        assert_eq!(objects.namespaces[0].name_any(), "ci");
+        assert_eq!(objects.role_bindings[0].name_any(), "deployer");
+    }
+
+    #[test]
+    fn test_add_manifest_list_sources() {
+        let mut objects = Objects::default();
+        objects
+            .add_manifest(
+                r#"# kubectl get clusterrolebindings -o yaml
+apiVersion: v1
+items:
+- apiVersion: rbac.authorization.k8s.io/v1
+  kind: ClusterRoleBinding
+  metadata:
+    name: first
+  roleRef:
+    apiGroup: rbac.authorization.k8s.io
+    kind: ClusterRole
+    name: second
+- apiVersion: rbac.authorization.k8s.io/v1
+  kind: ClusterRoleBinding
+  metadata:
+    name: second
+  roleRef:
+    apiGroup: rbac.authorization.k8s.io
+    kind: ClusterRole
+    name: view
+kind: List
+"#,
+                Some("dump.yaml"),
+            )
+            .unwrap();
+
+        let lines: Vec<usize> = objects
+            .cluster_role_bindings
+            .iter()
+            .map(|crb| objects.source("ClusterRoleBinding", crb).unwrap().line)
+            .collect();
+        assert_eq!(lines, vec![7, 15]);
+    }
+}
@@ -1,21 +1,51 @@
+use std::fmt::Write;
+
 use anyhow::Result;
 use k8s_openapi::{
-    apimachinery::pkg::apis::meta::v1::{ObjectMeta, Time},
+    apimachinery::pkg::apis::meta::v1::Time,
     chrono::{Duration, Utc},
 };
 use serde::{Deserialize, Serialize};
 
+use crate::{
+    effective::{EffectiveFinding, Grant},
+    finding::{Finding, Scope},
+    report::{Reporter, ScanInfo},
+};
+
 // #[derive(Serialize, Deserialize, Debug)]
 // pub enum OutputFormat {
 //     RoleBinding,
 //     ClusterRoleBinding,
 // }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 pub enum SubKind {
     User,
     Group,
     ServiceAccount,
+    /// Any kind the API server does not know about, kept as written.
+    Other(String),
+}
+
+impl SubKind {
+    pub fn from_kind(kind: &str) -> Self {
+        match kind {
+            "User" => Self::User,
+            "Group" => Self::Group,
+            "ServiceAccount" => Self::ServiceAccount,
+            _ => Self::Other(kind.to_string()),
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::User => "User",
+            Self::Group => "Group",
+            Self::ServiceAccount => "ServiceAccount",
+            Self::Other(kind) => kind,
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -23,28 +53,63 @@ pub struct OutR {
     pub rb: String,
     pub ns: String,
     pub r: String,
-    pub age: ObjectMeta,
+    pub age: Option<Time>,
     pub subject: String,
     pub sub_kind: SubKind,
+    pub sub_ns: Option<String>,
+    pub sub_api_group: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct OutCR {
     pub crb: String,
     pub r: String,
-    pub age: ObjectMeta,
+    pub age: Option<Time>,
     pub subject: String,
     pub sub_kind: SubKind,
+    pub sub_ns: Option<String>,
+    pub sub_api_group: Option<String>,
+}
+
+/// The whole scan as one document, for `--format json` and `--format yaml`.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct Report {
+    pub findings: Vec<Finding>,
 }
 
 impl OutR {
+    /// One row per subject, none for cluster-wide findings.
+    pub fn from_finding(finding: &Finding) -> Vec<Self> {
+        let ns = match &finding.scope {
+            Scope::Namespace(ns) => ns,
+            Scope::Cluster => return Vec::new(),
+        };
+        finding
+            .subjects
+            .iter()
+            .map(|subject| Self {
+                rb: finding.binding.name.clone(),
+                ns: ns.clone(),
+                r: finding.role.name.clone(),
+                age: finding.creation_timestamp.clone(),
+                subject: subject.name.clone(),
+                sub_kind: subject.kind.clone(),
+                sub_ns: subject.namespace.clone(),
+                sub_api_group: subject.api_group.clone(),
+            })
+            .collect()
+    }
+
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         rb: String,
         ns: String,
         r: String,
-        age: ObjectMeta,
+        age: Option<Time>,
         subject: String,
         sub_kind: SubKind,
+        sub_ns: Option<String>,
+        sub_api_group: Option<String>,
     ) -> Self {
         Self {
             rb,
@@ -53,17 +118,41 @@ impl OutR {
             age,
             subject,
             sub_kind,
+            sub_ns,
+            sub_api_group,
         }
     }
 }
 
 impl OutCR {
+    /// One row per subject, none for namespaced findings.
+    pub fn from_finding(finding: &Finding) -> Vec<Self> {
+        if finding.scope != Scope::Cluster {
+            return Vec::new();
+        }
+        finding
+            .subjects
+            .iter()
+            .map(|subject| Self {
+                crb: finding.binding.name.clone(),
+                r: finding.role.name.clone(),
+                age: finding.creation_timestamp.clone(),
+                subject: subject.name.clone(),
+                sub_kind: subject.kind.clone(),
+                sub_ns: subject.namespace.clone(),
+                sub_api_group: subject.api_group.clone(),
+            })
+            .collect()
+    }
+
     pub fn new(
         crb: String,
         r: String,
-        age: ObjectMeta,
+        age: Option<Time>,
         subject: String,
         sub_kind: SubKind,
+        sub_ns: Option<String>,
+        sub_api_group: Option<String>,
     ) -> Self {
         Self {
             crb,
@@ -71,49 +160,62 @@ impl OutCR {
             age,
             subject,
             sub_kind,
+            sub_ns,
+            sub_api_group,
         }
     }
 }
 
 pub fn output_r(result: Vec<OutR>) -> Result<()> {
+    print!("{}", table_r(&result)?);
+    Ok(())
+}
+
+pub fn output_cr(result: Vec<OutCR>) -> Result<()> {
+    print!("{}", table_cr(&result)?);
+    Ok(())
+}
+
+pub fn table_r(result: &[OutR]) -> Result<String> {
+    let mut table = String::new();
     let rb_max_name = result.iter().map(|x| x.rb.len() + 2).max().unwrap_or(63);
     let r_max_name = result.iter().map(|x| x.r.len() + 2).max().unwrap_or(63);
     let subj_max_name = result
         .iter()
-        .map(|x| x.subject.len() + 2)
+        .map(|x| format_subject(&x.subject, &x.sub_ns).len() + 2)
         .max()
         .unwrap_or(63);
-    println!(
+    writeln!(
+        table,
         "{0:<rb_max_name$} {1:<20} {2:<r_max_name$} {3:<20} {4:<subj_max_name$} {5:<20}",
         "ROLEBINDING", "NAMESPACE", "ROLE", "AGE", "SUBJECT", "SUBJECTKIND",
-    );
+    )?;
     for inst in result {
-        let age = format_creation_since(inst.age.creation_timestamp);
-        println!(
+        let age = format_creation_since(inst.age.clone());
+        writeln!(
+            table,
             "{0:<rb_max_name$} {1:<20} {2:<r_max_name$} {3:<20} {4:<subj_max_name$} {5:<20}",
             inst.rb,
             inst.ns,
             inst.r,
             age,
-            inst.subject,
-            match inst.sub_kind {
-                SubKind::User => "User",
-                SubKind::Group => "Group",
-                SubKind::ServiceAccount => "ServiceAccount",
-            },
-        );
+            format_subject(&inst.subject, &inst.sub_ns),
+            inst.sub_kind.as_str(),
+        )?;
     }
-    Ok(())
+    Ok(table)
 }
 
-pub fn output_cr(result: Vec<OutCR>) -> Result<()> {
+pub fn table_cr(result: &[OutCR]) -> Result<String> {
+    let mut table = String::new();
     let max_name = result.iter().map(|x| x.crb.len() + 2).max().unwrap_or(63);
     let subj_max_name = result
         .iter()
-        .map(|x| x.subject.len() + 2)
+        .map(|x| format_subject(&x.subject, &x.sub_ns).len() + 2)
         .max()
         .unwrap_or(63);
-    println!(
+    writeln!(
+        table,
         "{0:<width$} {1:<width$} {2:<20} {3:<subj_max_name$} {4:<20}",
         "CLUSTERROLEBINDING",
         "ROLE",
@@ -121,28 +223,247 @@ pub fn output_cr(result: Vec<OutCR>) -> Result<()> {
         "SUBJECT",
         "SUBJECTKIND",
         width = max_name
-    );
+    )?;
     for inst in result {
-        let age = format_creation_since(inst.age.creation_timestamp);
-        println!(
+        let age = format_creation_since(inst.age.clone());
+        writeln!(
+            table,
             "{0:<width$} {1:<width$} {2:<20} {3:<subj_max_name$} {4:<20}",
             inst.crb,
             inst.r,
             age,
-            inst.subject,
-            match inst.sub_kind {
-                SubKind::User => "User",
-                SubKind::Group => "Group",
-                SubKind::ServiceAccount => "ServiceAccount",
-            },
+            format_subject(&inst.subject, &inst.sub_ns),
+            inst.sub_kind.as_str(),
             width = max_name
-        );
+        )?;
     }
-    Ok(())
+    Ok(table)
+}
+
+/// Rules the way `kubectl auth can-i --list` shows them, with the bindings
+/// granting each.
+pub fn table_grants(grants: &[Grant]) -> Result<String> {
+    let mut table = String::new();
+    let rows: Vec<[String; 5]> = grants
+        .iter()
+        .map(|grant| {
+            let rule = &grant.rule;
+            let resources: Vec<String> = rule
+                .resources
+                .iter()
+                .flat_map(|resource| {
+                    rule.api_groups
+                        .iter()
+                        .map(move |group| match group.as_str() {
+                            "" => resource.clone(),
+                            group => format!("{resource}.{group}"),
+                        })
+                })
+                .collect();
+            let bindings: Vec<String> = grant.bindings.iter().map(|b| b.to_string()).collect();
+            [
+                resources.join(","),
+                rule.non_resource_urls.join(","),
+                rule.resource_names.join(","),
+                rule.verbs.join(","),
+                bindings.join(", "),
+            ]
+        })
+        .collect();
+    let width = |column: usize, header: &str| {
+        rows.iter()
+            .map(|row| row[column].len())
+            .chain([header.len()])
+            .max()
+            .unwrap_or_default()
+            + 2
+    };
+    let (res_w, url_w, names_w, verbs_w) = (
+        width(0, "RESOURCES"),
+        width(1, "NON-RESOURCE URLS"),
+        width(2, "RESOURCE NAMES"),
+        width(3, "VERBS"),
+    );
+    writeln!(
+        table,
+        "{0:<res_w$} {1:<url_w$} {2:<names_w$} {3:<verbs_w$} BINDINGS",
+        "RESOURCES", "NON-RESOURCE URLS", "RESOURCE NAMES", "VERBS",
+    )?;
+    for [resources, urls, names, verbs, bindings] in &rows {
+        writeln!(
+            table,
+            "{0:<res_w$} {1:<url_w$} {2:<names_w$} {3:<verbs_w$} {4}",
+            resources, urls, names, verbs, bindings,
+        )?;
+    }
+    Ok(table)
+}
+
+/// One row per check matched by merged permissions, `yes` under COMBINED
+/// when only several bindings together match it.
+pub fn table_effective(findings: &[EffectiveFinding]) -> Result<String> {
+    let mut table = String::new();
+    let scope_w = findings
+        .iter()
+        .map(|f| f.scope.to_string().len() + 2)
+        .max()
+        .unwrap_or(20);
+    let title_w = findings
+        .iter()
+        .map(|f| f.title.len() + 2)
+        .max()
+        .unwrap_or(20);
+    writeln!(
+        table,
+        "{0:<10} {1:<8} {2:<scope_w$} {3:<title_w$} {4:<8} BINDINGS",
+        "CHECK", "SEVERITY", "SCOPE", "TITLE", "COMBINED",
+    )?;
+    for finding in findings {
+        let bindings: Vec<String> = finding.bindings.iter().map(|b| b.to_string()).collect();
+        writeln!(
+            table,
+            "{0:<10} {1:<8} {2:<scope_w$} {3:<title_w$} {4:<8} {5}",
+            finding.check,
+            finding.severity,
+            finding.scope.to_string(),
+            finding.title,
+            if finding.combined { "yes" } else { "no" },
+            bindings.join(", "),
+        )?;
+    }
+    Ok(table)
 }
 
+/// ServiceAccounts are shown as `namespace/name`.
+fn format_subject(subject: &str, sub_ns: &Option<String>) -> String {
+    match sub_ns {
+        Some(ns) => format!("{ns}/{subject}"),
+        None => subject.to_string(),
+    }
+}
+
+/// The tables of every check with findings, in the order the checks ran.
+#[derive(Default)]
+pub struct TableReporter {
+    findings: Vec<Finding>,
+}
+
+impl TableReporter {
+    fn flush(&mut self) -> Result<()> {
+        print!("{}", self.tables()?);
+        Ok(())
+    }
+
+    /// System and suppressed findings get tables of their own.
+    fn tables(&mut self) -> Result<String> {
+        let findings = std::mem::take(&mut self.findings);
+        let mut tables = String::new();
+        for note in ["", " (system)", " (suppressed)"] {
+            let group: Vec<Finding> = findings
+                .iter()
+                .filter(|finding| Self::note(finding) == note)
+                .cloned()
+                .collect();
+            tables.push_str(&Self::render(&group, note)?);
+        }
+        Ok(tables)
+    }
+
+    fn note(finding: &Finding) -> &'static str {
+        match (&finding.suppressed, finding.system) {
+            (Some(_), _) => " (suppressed)",
+            (None, true) => " (system)",
+            (None, false) => "",
+        }
+    }
+
+    fn render(findings: &[Finding], note: &str) -> Result<String> {
+        let mut output = String::new();
+        if let Some(head) = findings.first() {
+            writeln!(
+                output,
+                "[{}] {} {}{}",
+                head.severity, head.check, head.title, note
+            )?;
+        }
+        let out_r_list: Vec<OutR> = findings.iter().flat_map(OutR::from_finding).collect();
+        let out_cr_list: Vec<OutCR> = findings.iter().flat_map(OutCR::from_finding).collect();
+        if !out_r_list.is_empty() {
+            output.push_str(&table_r(&out_r_list)?);
+        }
+        if !out_cr_list.is_empty() {
+            output.push_str(&table_cr(&out_cr_list)?);
+        }
+        for finding in findings {
+            for workload in &finding.workloads {
+                writeln!(
+                    output,
+                    "{} inherits {}",
+                    workload.summary(),
+                    finding.binding
+                )?;
+            }
+        }
+        Ok(output)
+    }
+}
+
+impl Reporter for TableReporter {
+    fn finding(&mut self, finding: &Finding) -> Result<()> {
+        if matches!(self.findings.first(), Some(head) if head.check != finding.check) {
+            self.flush()?;
+        }
+        self.findings.push(finding.clone());
+        Ok(())
+    }
+
+    fn end(&mut self, _info: &ScanInfo) -> Result<()> {
+        self.flush()
+    }
+}
+
+/// The whole scan as one JSON document.
+#[derive(Default)]
+pub struct JsonReporter {
+    report: Report,
+}
+
+impl Reporter for JsonReporter {
+    fn finding(&mut self, finding: &Finding) -> Result<()> {
+        self.report.findings.push(finding.clone());
+        Ok(())
+    }
+
+    fn end(&mut self, _info: &ScanInfo) -> Result<()> {
+        println!("{}", serde_json::to_string_pretty(&self.report)?);
+        Ok(())
+    }
+}
+
+/// The whole scan as one YAML document.
+#[derive(Default)]
+pub struct YamlReporter {
+    report: Report,
+}
+
+impl Reporter for YamlReporter {
+    fn finding(&mut self, finding: &Finding) -> Result<()> {
+        self.report.findings.push(finding.clone());
+        Ok(())
+    }
+
+    fn end(&mut self, _info: &ScanInfo) -> Result<()> {
+        print!("{}", serde_yaml::to_string(&self.report)?);
+        Ok(())
+    }
+}
+
+/// Manifests usually come without a creationTimestamp.
 fn format_creation_since(time: Option<Time>) -> String {
-    format_duration(Utc::now().signed_duration_since(time.unwrap().0))
+    match time {
+        Some(time) => format_duration(Utc::now().signed_duration_since(time.0)),
+        None => "-".to_string(),
+    }
 }
 
 fn format_duration(dur: Duration) -> String {
@@ -152,3 +473,221 @@ fn format_duration(dur: Duration) -> String {
         (_, _, mins) => format!("{mins}m"),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    use crate::{Access, Check, Exceptions, Objects};
+
+    #[test]
+    fn test_table_without_timestamp() {
+        let out_cr = OutCR::new(
+            "readers".to_string(),
+            "view".to_string(),
+            None,
+            "alice".to_string(),
+            SubKind::User,
+            None,
+            None,
+        );
+        let table = table_cr(&[out_cr]).unwrap();
+        let row: Vec<&str> = table.lines().nth(1).unwrap().split_whitespace().collect();
+        assert_eq!(row, vec!["readers", "view", "-", "alice", "User"]);
+    }
+
+    #[tokio::test]
+    async fn test_report_serialization() {
+        let check = Check::from_yaml(include_str!("../fixtures/check.yaml")).unwrap();
+        let mut objects = Objects::default();
+        objects
+            .add_manifest(
+                r#"
+apiVersion: rbac.authorization.k8s.io/v1
+kind: ClusterRole
+metadata:
+  name: everything
+rules:
+  - {apiGroups: ["*"], resources: ["*"], verbs: ["*"]}
+---
+apiVersion: rbac.authorization.k8s.io/v1
+kind: ClusterRoleBinding
+metadata:
+  name: admins
+roleRef: {apiGroup: rbac.authorization.k8s.io, kind: ClusterRole, name: everything}
+subjects:
+  - {kind: User, name: alice}
+  - {kind: ServiceAccount, name: deployer, namespace: ci}
+"#,
+                None,
+            )
+            .unwrap();
+        let report = Report {
+            findings: check.run(&Access::from_objects(objects)).await.unwrap(),
+        };
+        assert!(!report.findings.is_empty());
+        for finding in &report.findings {
+            assert_eq!(
+                serde_json::to_value(&finding.binding).unwrap(),
+                json!({"kind": "ClusterRoleBinding", "name": "admins"})
+            );
+            assert_eq!(
+                serde_json::to_value(&finding.subjects).unwrap(),
+                json!([
+                    {"kind": "User", "name": "alice"},
+                    {"kind": "ServiceAccount", "name": "deployer", "namespace": "ci"},
+                ])
+            );
+        }
+
+        let json = serde_json::to_string_pretty(&report).unwrap();
+        let parsed: Report = serde_json::from_str(&json).unwrap();
+        assert_eq!(serde_json::to_string_pretty(&parsed).unwrap(), json);
+
+        let yaml = serde_yaml::to_string(&report).unwrap();
+        let parsed: Report = serde_yaml::from_str(&yaml).unwrap();
+        assert_eq!(serde_yaml::to_string(&parsed).unwrap(), yaml);
+    }
+
+    #[tokio::test]
+    async fn test_row_per_subject() {
+        let check = Check::from_yaml(include_str!("../fixtures/check.yaml")).unwrap();
+        let access = Access::with_secret_reader(
+            r#"
+apiVersion: rbac.authorization.k8s.io/v1
+kind: RoleBinding
+metadata:
+  name: read-secrets
+  namespace: ci
+roleRef: {apiGroup: rbac.authorization.k8s.io, kind: ClusterRole, name: secret-reader}
+subjects:
+  - {kind: User, name: alice}
+  - {kind: Group, name: devs}
+  - {kind: ServiceAccount, name: deployer, namespace: ci}
+"#,
+            None,
+        );
+        let findings = check.run(&access).await.unwrap();
+        let findings: Vec<Finding> = findings
+            .into_iter()
+            .filter(|finding| finding.check == "RBACR-002")
+            .collect();
+        assert_eq!(findings.len(), 1);
+
+        let out_r_list: Vec<OutR> = findings.iter().flat_map(OutR::from_finding).collect();
+        let table = table_r(&out_r_list).unwrap();
+        let subjects: Vec<(&str, &str)> = table
+            .lines()
+            .skip(1)
+            .map(|line| {
+                let row: Vec<&str> = line.split_whitespace().collect();
+                (row[0], row[4])
+            })
+            .collect();
+        assert_eq!(
+            subjects,
+            vec![
+                ("read-secrets", "alice"),
+                ("read-secrets", "devs"),
+                ("read-secrets", "ci/deployer"),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_unknown_subject_kind() {
+        let check = Check::from_yaml(include_str!("../fixtures/check.yaml")).unwrap();
+        let access = Access::with_secret_reader(
+            r#"
+apiVersion: rbac.authorization.k8s.io/v1
+kind: ClusterRoleBinding
+metadata:
+  name: read-secrets
+roleRef: {apiGroup: rbac.authorization.k8s.io, kind: ClusterRole, name: secret-reader}
+subjects:
+  - {kind: Robot, name: r2d2, apiGroup: example.com}
+  - {kind: User, name: alice}
+"#,
+            None,
+        );
+        let findings = check.run(&access).await.unwrap();
+        let finding = findings
+            .iter()
+            .find(|finding| finding.check == "RBACR-002")
+            .unwrap();
+        let kinds: Vec<&SubKind> = finding.subjects.iter().map(|s| &s.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![&SubKind::Other("Robot".to_string()), &SubKind::User]
+        );
+
+        let table = table_cr(&OutCR::from_finding(finding)).unwrap();
+        let row: Vec<&str> = table.lines().nth(1).unwrap().split_whitespace().collect();
+        assert_eq!(row[3..], ["r2d2", "Robot"]);
+
+        let report = Report {
+            findings: vec![finding.clone()],
+        };
+        let json: Report = serde_json::from_str(&serde_json::to_string(&report).unwrap()).unwrap();
+        let yaml: Report = serde_yaml::from_str(&serde_yaml::to_string(&report).unwrap()).unwrap();
+        for report in [json, yaml] {
+            let subject = &report.findings[0].subjects[0];
+            assert_eq!(subject.kind.as_str(), "Robot");
+            assert_eq!(subject.kind, SubKind::from_kind("Robot"));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_table_reporter_notes() {
+        let mut check = Check::from_yaml(include_str!("../fixtures/check.yaml")).unwrap();
+        check.exceptions = Exceptions::from_yaml("exceptions:\n  - subject: bob\n").unwrap();
+        check.show_suppressed = true;
+        let access = Access::with_secret_reader(
+            r#"
+apiVersion: rbac.authorization.k8s.io/v1
+kind: ClusterRoleBinding
+metadata:
+  name: readers
+roleRef: {apiGroup: rbac.authorization.k8s.io, kind: ClusterRole, name: secret-reader}
+subjects:
+  - {kind: User, name: alice}
+  - {kind: User, name: bob}
+  - {kind: Group, name: "system:masters"}
+"#,
+            None,
+        );
+        let mut reporter = TableReporter::default();
+        for finding in check.run(&access).await.unwrap() {
+            if finding.check == "RBACR-002" {
+                reporter.finding(&finding).unwrap();
+            }
+        }
+        let tables = reporter.tables().unwrap();
+
+        // Each heading is followed by the column titles and one row per subject.
+        let mut sections: Vec<(&str, Vec<&str>)> = Vec::new();
+        for line in tables.lines() {
+            match line.starts_with('[') {
+                true => sections.push((line, Vec::new())),
+                false => sections.last_mut().unwrap().1.push(line),
+            }
+        }
+        let item = check.get_check_item("RBACR-002").unwrap();
+        let heading = format!("[{}] RBACR-002 {}", item.severity, item.title);
+        let expected = [
+            ("", "alice"),
+            (" (system)", "system:masters"),
+            (" (suppressed)", "bob"),
+        ];
+        assert_eq!(sections.len(), expected.len());
+        for ((title, lines), (note, subject)) in sections.iter().zip(expected) {
+            assert_eq!(*title, format!("{heading}{note}"));
+            let subjects: Vec<&str> = lines[1..]
+                .iter()
+                .map(|line| line.split_whitespace().nth(3).unwrap())
+                .collect();
+            assert_eq!(subjects, vec![subject]);
+        }
+    }
+}
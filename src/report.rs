@@ -0,0 +1,113 @@
+use anyhow::Result;
+use k8s_openapi::chrono::{DateTime, Utc};
+
+use crate::{check::Check, finding::Finding};
+
+/// What a reporter is told about the scan around its findings.
+pub struct ScanInfo<'a> {
+    pub check: &'a Check,
+    /// The manifest path or kubeconfig context that was scanned.
+    pub target: &'a str,
+    pub started: DateTime<Utc>,
+}
+
+/// Receives the findings of `Check::report` as the checks produce them, the
+/// findings of one check arrive one after another.
+pub trait Reporter {
+    fn begin(&mut self, _info: &ScanInfo) -> Result<()> {
+        Ok(())
+    }
+
+    fn finding(&mut self, finding: &Finding) -> Result<()>;
+
+    fn end(&mut self, _info: &ScanInfo) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Access;
+
+    /// Writes down every call it receives.
+    #[derive(Default)]
+    struct Recorder {
+        calls: Vec<String>,
+    }
+
+    impl Reporter for Recorder {
+        fn begin(&mut self, info: &ScanInfo) -> Result<()> {
+            self.calls.push(format!("begin {}", info.target));
+            Ok(())
+        }
+
+        fn finding(&mut self, finding: &Finding) -> Result<()> {
+            self.calls.push(finding.check.clone());
+            Ok(())
+        }
+
+        fn end(&mut self, info: &ScanInfo) -> Result<()> {
+            self.calls.push(format!("end {}", info.target));
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_report_calls() {
+        let check = Check::from_yaml(include_str!("../fixtures/check.yaml")).unwrap();
+        let access = Access::with_secret_reader(
+            r#"
+apiVersion: rbac.authorization.k8s.io/v1
+kind: ClusterRoleBinding
+metadata:
+  name: readers
+roleRef: {apiGroup: rbac.authorization.k8s.io, kind: ClusterRole, name: secret-reader}
+subjects:
+  - {kind: User, name: alice}
+  - {kind: Group, name: "system:masters"}
+---
+apiVersion: rbac.authorization.k8s.io/v1
+kind: Role
+metadata:
+  name: impersonator
+  namespace: ci
+rules:
+  - {apiGroups: [""], resources: ["users"], verbs: ["impersonate"]}
+---
+apiVersion: rbac.authorization.k8s.io/v1
+kind: RoleBinding
+metadata:
+  name: ci-impersonators
+  namespace: ci
+roleRef: {apiGroup: rbac.authorization.k8s.io, kind: Role, name: impersonator}
+subjects:
+  - {kind: User, name: deployer}
+"#,
+            None,
+        );
+        let mut recorder = Recorder::default();
+        let findings = check
+            .report(&access, "rbac.yaml", &mut recorder)
+            .await
+            .unwrap();
+
+        let calls = &recorder.calls;
+        assert_eq!(calls.first().unwrap(), "begin rbac.yaml");
+        assert_eq!(calls.last().unwrap(), "end rbac.yaml");
+        let checks: Vec<&String> = calls[1..calls.len() - 1].iter().collect();
+        assert_eq!(
+            checks,
+            findings.iter().map(|f| &f.check).collect::<Vec<_>>()
+        );
+
+        // Each check shows up in a single run, and the runs follow the ids.
+        let mut runs = checks.clone();
+        runs.dedup();
+        let mut ids = runs.clone();
+        ids.sort();
+        ids.dedup();
+        assert_eq!(runs, ids);
+        assert!(runs.len() > 1 && checks.len() > runs.len());
+    }
+}
@@ -0,0 +1,186 @@
+use anyhow::Result;
+use k8s_openapi::chrono::SecondsFormat;
+use serde_json::{json, Value};
+
+use crate::{
+    check::{Check, Severity},
+    finding::Finding,
+    report::{Reporter, ScanInfo},
+};
+
+const SARIF_SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+
+#[derive(Default)]
+pub struct SarifReporter {
+    findings: Vec<Finding>,
+}
+
+impl Reporter for SarifReporter {
+    fn finding(&mut self, finding: &Finding) -> Result<()> {
+        self.findings.push(finding.clone());
+        Ok(())
+    }
+
+    fn end(&mut self, info: &ScanInfo) -> Result<()> {
+        let mut report = sarif_report(info.check, &self.findings)?;
+        report["runs"][0]["invocations"] = json!([{
+            "executionSuccessful": true,
+            "startTimeUtc": info.started.to_rfc3339_opts(SecondsFormat::Secs, true),
+        }]);
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        Ok(())
+    }
+}
+
+/// A SARIF 2.1.0 log with one rule per check and one result per finding.
+pub fn sarif_report(check: &Check, findings: &[Finding]) -> Result<Value> {
+    let check_list = check.get_check()?;
+    let rules: Vec<Value> = check
+        .itemlist
+        .values()
+        .map(|item| {
+            let mut rule = json!({
+                "id": item.id,
+                "name": item.title,
+                "shortDescription": { "text": item.title },
+                "defaultConfiguration": { "level": level(item.severity) },
+                "properties": { "security-severity": security_severity(item.severity) },
+            });
+            if !item.description.is_empty() {
+                rule["fullDescription"] = json!({ "text": item.description });
+            }
+            if !item.remediation.is_empty() {
+                rule["help"] = json!({ "text": item.remediation });
+            }
+            if let Some(reference) = item.references.first() {
+                rule["helpUri"] = json!(reference);
+            }
+            rule
+        })
+        .collect();
+
+    let results: Vec<Value> = findings
+        .iter()
+        .map(|finding| {
+            let mut result = json!({
+                "ruleId": finding.check,
+                "level": level(finding.severity),
+                "message": { "text": finding.summary() },
+                "locations": [location(finding)],
+            });
+            if let Some(index) = check_list.iter().position(|key| key == &finding.check) {
+                result["ruleIndex"] = json!(index);
+            }
+            if finding.system {
+                result["properties"] = json!({ "tags": ["system"] });
+            }
+            if let Some(suppression) = &finding.suppressed {
+                let mut entry = json!({ "kind": "external" });
+                if let Some(justification) = &suppression.justification {
+                    entry["justification"] = json!(justification);
+                }
+                result["suppressions"] = json!([entry]);
+            }
+            result
+        })
+        .collect();
+
+    Ok(json!({
+        "$schema": SARIF_SCHEMA,
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": env!("CARGO_PKG_NAME"),
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "informationUri": env!("CARGO_PKG_REPOSITORY"),
+                    "rules": rules,
+                }
+            },
+            "results": results,
+        }]
+    }))
+}
+
+fn level(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Critical | Severity::High => "error",
+        Severity::Medium => "warning",
+        Severity::Low | Severity::Info => "note",
+    }
+}
+
+/// The CVSS-like score code-scanning dashboards rank alerts by.
+fn security_severity(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Critical => "9.5",
+        Severity::High => "8.0",
+        Severity::Medium => "5.5",
+        Severity::Low => "3.0",
+        Severity::Info => "0.0",
+    }
+}
+
+/// The binding document when scanning manifests, its name otherwise.
+fn location(finding: &Finding) -> Value {
+    let mut location = json!({
+        "logicalLocations": [{
+            "name": finding.binding.name,
+            "fullyQualifiedName": finding.binding.to_string(),
+            "kind": "object",
+        }]
+    });
+    if let Some(source) = &finding.source {
+        location["physicalLocation"] = json!({
+            "artifactLocation": { "uri": source.file.trim_start_matches("./") },
+            "region": { "startLine": source.line },
+        });
+    }
+    location
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Access;
+
+    #[tokio::test]
+    async fn test_sarif_report() {
+        let check = Check::from_yaml(include_str!("../fixtures/check.yaml")).unwrap();
+        let access = Access::with_secret_reader(
+            r#"
+# Bound to every authenticated user by mistake.
+apiVersion: rbac.authorization.k8s.io/v1
+kind: ClusterRoleBinding
+metadata:
+  name: readers
+roleRef: {apiGroup: rbac.authorization.k8s.io, kind: ClusterRole, name: secret-reader}
+subjects:
+  - {kind: Group, name: "system:authenticated"}
+"#,
+            Some("./deploy/rbac.yaml"),
+        );
+        let findings = check.run(&access).await.unwrap();
+        let report = sarif_report(&check, &findings).unwrap();
+
+        assert_eq!(report["version"], "2.1.0");
+        let run = &report["runs"][0];
+        let rules = run["tool"]["driver"]["rules"].as_array().unwrap();
+        let ids: Vec<&str> = rules
+            .iter()
+            .map(|rule| rule["id"].as_str().unwrap())
+            .collect();
+        assert_eq!(ids, check.get_check().unwrap());
+
+        let results = run["results"].as_array().unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0]["ruleId"], "RBACR-002");
+        assert_eq!(
+            rules[results[0]["ruleIndex"].as_u64().unwrap() as usize]["id"],
+            "RBACR-002"
+        );
+        let physical = &results[0]["locations"][0]["physicalLocation"];
+        assert_eq!(physical["artifactLocation"]["uri"], "deploy/rbac.yaml");
+        assert_eq!(physical["region"]["startLine"], 10);
+    }
+}
@@ -0,0 +1,266 @@
+use std::fmt::Write;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    check::Severity,
+    exception::Suppression,
+    finding::{is_false, Finding, ObjectRef, Scope, SubjectInfo},
+    report::{Reporter, ScanInfo},
+    workload::Workload,
+};
+
+/// Everything one subject is reported for, across all checks.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SubjectSummary {
+    pub subject: SubjectInfo,
+    /// The weights of the distinct checks the subject triggers added up,
+    /// suppressed findings left out. See [`Severity::weight`].
+    pub score: u32,
+    pub findings: Vec<SubjectFinding>,
+    /// Workloads running as the subject, inheriting its permissions.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub workloads: Vec<Workload>,
+}
+
+/// A finding as seen from one of its subjects.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SubjectFinding {
+    pub check: String,
+    pub title: String,
+    pub severity: Severity,
+    pub binding: ObjectRef,
+    pub role: ObjectRef,
+    pub scope: Scope,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub suppressed: Option<Suppression>,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub system: bool,
+}
+
+/// The subjects of a scan, for `--group-by subject` with `--format json`
+/// and `--format yaml`.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct SubjectReport {
+    pub subjects: Vec<SubjectSummary>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SubjectFormat {
+    Table,
+    Json,
+    Yaml,
+}
+
+/// Prints the findings keyed by subject once the scan is done.
+pub struct SubjectReporter {
+    format: SubjectFormat,
+    findings: Vec<Finding>,
+}
+
+impl SubjectReporter {
+    pub fn new(format: SubjectFormat) -> Self {
+        Self {
+            format,
+            findings: Vec::new(),
+        }
+    }
+}
+
+impl Reporter for SubjectReporter {
+    fn finding(&mut self, finding: &Finding) -> Result<()> {
+        self.findings.push(finding.clone());
+        Ok(())
+    }
+
+    fn end(&mut self, _info: &ScanInfo) -> Result<()> {
+        let report = SubjectReport {
+            subjects: subject_report(&self.findings),
+        };
+        match self.format {
+            SubjectFormat::Table => print!("{}", table_subjects(&report.subjects)?),
+            SubjectFormat::Json => println!("{}", serde_json::to_string_pretty(&report)?),
+            SubjectFormat::Yaml => print!("{}", serde_yaml::to_string(&report)?),
+        }
+        Ok(())
+    }
+}
+
+/// Regroup findings by subject, riskiest subjects first.
+pub fn subject_report(findings: &[Finding]) -> Vec<SubjectSummary> {
+    let mut summaries: Vec<SubjectSummary> = Vec::new();
+    for finding in findings {
+        for subject in &finding.subjects {
+            let entry = SubjectFinding {
+                check: finding.check.clone(),
+                title: finding.title.clone(),
+                severity: finding.severity,
+                binding: finding.binding.clone(),
+                role: finding.role.clone(),
+                scope: finding.scope.clone(),
+                suppressed: finding.suppressed.clone(),
+                system: finding.system,
+            };
+            let index = match summaries
+                .iter()
+                .position(|summary| same_subject(&summary.subject, subject))
+            {
+                Some(index) => index,
+                None => {
+                    summaries.push(SubjectSummary {
+                        subject: subject.clone(),
+                        score: 0,
+                        findings: Vec::new(),
+                        workloads: Vec::new(),
+                    });
+                    summaries.len() - 1
+                }
+            };
+            let summary = &mut summaries[index];
+            summary.findings.push(entry);
+            let namespace = finding.binding.namespace.as_deref();
+            for workload in &finding.workloads {
+                if workload.runs_as(subject, namespace) && !summary.workloads.contains(workload) {
+                    summary.workloads.push(workload.clone());
+                }
+            }
+        }
+    }
+    for summary in &mut summaries {
+        summary.score = score(&summary.findings);
+    }
+    summaries.sort_by(|a, b| {
+        b.score
+            .cmp(&a.score)
+            .then_with(|| a.subject.to_string().cmp(&b.subject.to_string()))
+    });
+    summaries
+}
+
+/// A check counts once however many bindings trigger it.
+fn score(findings: &[SubjectFinding]) -> u32 {
+    let mut checks: Vec<&str> = Vec::new();
+    let mut score = 0;
+    for finding in findings.iter().filter(|f| f.suppressed.is_none()) {
+        if !checks.contains(&finding.check.as_str()) {
+            checks.push(&finding.check);
+            score += finding.severity.weight();
+        }
+    }
+    score
+}
+
+fn same_subject(a: &SubjectInfo, b: &SubjectInfo) -> bool {
+    a.kind == b.kind && a.name == b.name && a.namespace == b.namespace
+}
+
+/// One block per subject, headed by its score, with a row per finding.
+pub fn table_subjects(summaries: &[SubjectSummary]) -> Result<String> {
+    let mut table = String::new();
+    let findings = summaries.iter().flat_map(|summary| &summary.findings);
+    let scope_w = findings
+        .clone()
+        .map(|f| f.scope.to_string().len() + 2)
+        .max()
+        .unwrap_or(20);
+    let binding_w = findings
+        .clone()
+        .map(|f| f.binding.to_string().len() + 2)
+        .max()
+        .unwrap_or(20);
+    let role_w = findings
+        .map(|f| f.role.to_string().len() + 2)
+        .max()
+        .unwrap_or(20);
+    for summary in summaries {
+        writeln!(table, "[{}] {}", summary.score, summary.subject)?;
+        for workload in &summary.workloads {
+            writeln!(table, "run by {}", workload.summary())?;
+        }
+        writeln!(
+            table,
+            "{0:<10} {1:<8} {2:<scope_w$} {3:<binding_w$} {4:<role_w$} {5:<12} TITLE",
+            "CHECK", "SEVERITY", "SCOPE", "BINDING", "ROLE", "NOTE",
+        )?;
+        for finding in &summary.findings {
+            let note = match (&finding.suppressed, finding.system) {
+                (Some(_), _) => "suppressed",
+                (None, true) => "system",
+                (None, false) => "",
+            };
+            writeln!(
+                table,
+                "{0:<10} {1:<8} {2:<scope_w$} {3:<binding_w$} {4:<role_w$} {5:<12} {6}",
+                finding.check,
+                finding.severity,
+                finding.scope.to_string(),
+                finding.binding.to_string(),
+                finding.role.to_string(),
+                note,
+                finding.title,
+            )?;
+        }
+        writeln!(table)?;
+    }
+    Ok(table)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SubKind;
+
+    fn finding(check: &str, severity: Severity, binding: &str, subjects: &[&str]) -> Finding {
+        let object = |kind: &str| ObjectRef {
+            kind: kind.to_string(),
+            name: binding.to_string(),
+            namespace: None,
+        };
+        Finding {
+            check: check.to_string(),
+            title: check.to_string(),
+            severity,
+            binding: object("ClusterRoleBinding"),
+            role: object("ClusterRole"),
+            scope: Scope::Cluster,
+            subjects: subjects
+                .iter()
+                .map(|name| SubjectInfo {
+                    kind: SubKind::User,
+                    name: name.to_string(),
+                    namespace: None,
+                    api_group: None,
+                })
+                .collect(),
+            creation_timestamp: None,
+            rules: Vec::new(),
+            source: None,
+            suppressed: None,
+            system: false,
+            workloads: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_subject_report() {
+        let mut suppressed = finding("RBACR-006", Severity::Critical, "bind", &["bob"]);
+        suppressed.suppressed = Some(Suppression {
+            expires: None,
+            justification: None,
+        });
+        let findings = vec![
+            finding("RBACR-002", Severity::High, "secrets", &["alice", "bob"]),
+            finding("RBACR-002", Severity::High, "more-secrets", &["alice"]),
+            finding("RBACR-003", Severity::High, "workloads", &["bob"]),
+            suppressed,
+        ];
+        let report = subject_report(&findings);
+        let scores: Vec<(&str, u32, usize)> = report
+            .iter()
+            .map(|s| (s.subject.name.as_str(), s.score, s.findings.len()))
+            .collect();
+        let high = Severity::High.weight();
+        assert_eq!(scores, vec![("bob", 2 * high, 3), ("alice", high, 2)]);
+    }
+}
@@ -0,0 +1,127 @@
+use std::fmt;
+
+use crate::check::{Check, CheckItem, Kind, NameScope, ResourceNames, Rules, Severity};
+
+/// The id findings of a `who-can` query are reported under.
+pub const WHO_CAN: &str = "who-can";
+
+/// A permission to list the holders of, asked the way `kubectl auth can-i`
+/// takes it. Resources starting with `/` are nonResourceURLs.
+#[derive(Debug, Clone, Default)]
+pub struct WhoCan {
+    pub verb: String,
+    pub resource: String,
+    pub subresource: Option<String>,
+    /// `""` is the core group, `deployments.apps` style resources name their own.
+    pub api_group: String,
+    /// Also count rules limited by `resourceNames` to this name.
+    pub name: Option<String>,
+}
+
+impl WhoCan {
+    /// A check with the query as its only item, every subject of a binding
+    /// granting the permission is reported like any other finding.
+    pub fn check(&self) -> Check {
+        let mut rules = Rules::new(None, vec![self.verb.clone()]);
+        if self.resource.starts_with('/') {
+            rules.non_resource_urls = Some(vec![self.resource.clone()]);
+        } else {
+            let (resource, api_group) = match self.resource.split_once('.') {
+                Some((resource, api_group)) if self.api_group.is_empty() => (resource, api_group),
+                _ => (self.resource.as_str(), self.api_group.as_str()),
+            };
+            let resource = match &self.subresource {
+                Some(subresource) => format!("{resource}/{subresource}"),
+                None => resource.to_string(),
+            };
+            rules.api_groups = Some(vec![api_group.to_string()]);
+            rules.resources = Some(vec![resource]);
+            rules.resource_names = match &self.name {
+                Some(name) => ResourceNames::Names(vec![name.clone()]),
+                None => ResourceNames::Scope(NameScope::Unrestricted),
+            };
+        }
+
+        let mut item = CheckItem::new(Kind::All, Some(vec![rules]));
+        item.title = self.to_string();
+        item.severity = Severity::Info;
+        let mut check = Check::new();
+        check.add(WHO_CAN.to_string(), item);
+        check
+    }
+}
+
+impl fmt::Display for WhoCan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.verb, self.resource)?;
+        if let Some(subresource) = &self.subresource {
+            write!(f, "/{subresource}")?;
+        }
+        if !self.api_group.is_empty() {
+            write!(f, " in API group {}", self.api_group)?;
+        }
+        if let Some(name) = &self.name {
+            write!(f, " named {name}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Access, Objects};
+
+    #[tokio::test]
+    async fn test_who_can() {
+        let mut objects = Objects::default();
+        objects
+            .add_manifest(
+                r#"
+apiVersion: rbac.authorization.k8s.io/v1
+kind: Role
+metadata:
+  name: deployer
+  namespace: ci
+rules:
+  - apiGroups: ["apps"]
+    resources: ["deployments"]
+    verbs: ["create"]
+  - apiGroups: [""]
+    resources: ["pods/log"]
+    resourceNames: ["builder"]
+    verbs: ["get"]
+---
+apiVersion: rbac.authorization.k8s.io/v1
+kind: RoleBinding
+metadata:
+  name: deployer
+  namespace: ci
+roleRef: {apiGroup: rbac.authorization.k8s.io, kind: Role, name: deployer}
+subjects:
+  - {kind: ServiceAccount, name: deployer, namespace: ci}
+"#,
+                None,
+            )
+            .unwrap();
+        let access = Access::from_objects(objects);
+        let query = |verb: &str, resource: &str| WhoCan {
+            verb: verb.to_string(),
+            resource: resource.to_string(),
+            ..Default::default()
+        };
+
+        let check = query("create", "deployments.apps").check();
+        assert_eq!(check.run(&access).await.unwrap().len(), 1);
+        let check = query("create", "deployments").check();
+        assert!(check.run(&access).await.unwrap().is_empty());
+
+        let mut logs = query("get", "pods");
+        logs.subresource = Some("log".to_string());
+        assert!(logs.check().run(&access).await.unwrap().is_empty());
+        logs.name = Some("builder".to_string());
+        let findings = logs.check().run(&access).await.unwrap();
+        assert_eq!(findings[0].check, WHO_CAN);
+        assert_eq!(findings[0].binding.name, "deployer");
+    }
+}
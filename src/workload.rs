@@ -0,0 +1,360 @@
+use std::fmt;
+
+use anyhow::Result;
+use k8s_openapi::api::{
+    apps::v1::{DaemonSet, Deployment, ReplicaSet, StatefulSet},
+    batch::v1::{CronJob, Job},
+    core::v1::{Pod, PodSpec, ReplicationController},
+};
+use kube::{
+    api::{Api, ListParams, ResourceExt},
+    Client,
+};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use serde_yaml::{Mapping, Value};
+
+use crate::{access::Objects, finding::SubjectInfo, print::SubKind};
+
+/// Controllers whose pod templates are listed, the pods they create are left
+/// to them. Pods of any other controller are listed on their own.
+const POD_CONTROLLERS: [&str; 5] = [
+    "ReplicaSet",
+    "ReplicationController",
+    "DaemonSet",
+    "StatefulSet",
+    "Job",
+];
+
+/// A pod or the pod template of a controller, reduced to the ServiceAccount it
+/// runs as and the images running with its token.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Workload {
+    pub kind: String,
+    pub name: String,
+    pub namespace: String,
+    /// `default` when the pod spec names none.
+    pub service_account: String,
+    /// `automountServiceAccountToken` of the pod spec, when unset the
+    /// ServiceAccount decides and mounts the token by default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub automount_token: Option<bool>,
+    pub images: Vec<String>,
+}
+
+impl Workload {
+    pub fn from_pod_spec(kind: &str, name: String, namespace: String, spec: &PodSpec) -> Self {
+        let images = spec
+            .init_containers
+            .iter()
+            .flatten()
+            .chain(&spec.containers)
+            .filter_map(|container| container.image.clone())
+            .collect();
+        #[allow(deprecated)]
+        let service_account = spec
+            .service_account_name
+            .clone()
+            .or_else(|| spec.service_account.clone())
+            .unwrap_or_else(|| "default".to_string());
+        Self {
+            kind: kind.to_string(),
+            name,
+            namespace,
+            service_account,
+            automount_token: spec.automount_service_account_token,
+            images,
+        }
+    }
+
+    /// Whether the pods run as `subject`, directly or through one of the groups
+    /// every ServiceAccount is in. ServiceAccount subjects without a namespace
+    /// take that of the binding.
+    pub fn runs_as(&self, subject: &SubjectInfo, binding_namespace: Option<&str>) -> bool {
+        match subject.kind {
+            SubKind::ServiceAccount => {
+                subject.name == self.service_account
+                    && subject.namespace.as_deref().or(binding_namespace)
+                        == Some(self.namespace.as_str())
+            }
+            SubKind::User => {
+                subject.name
+                    == format!(
+                        "system:serviceaccount:{}:{}",
+                        self.namespace, self.service_account
+                    )
+            }
+            SubKind::Group => {
+                subject.name == "system:serviceaccounts"
+                    || subject.name == format!("system:serviceaccounts:{}", self.namespace)
+            }
+            SubKind::Other(_) => false,
+        }
+    }
+}
+
+impl Workload {
+    /// `Deployment ci/web (nginx:1.25)`, noting pods that don't mount the token.
+    pub fn summary(&self) -> String {
+        let mut summary = format!("{} ({})", self, self.images.join(", "));
+        if self.automount_token == Some(false) {
+            summary.push_str(" without token");
+        }
+        summary
+    }
+}
+
+impl fmt::Display for Workload {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}/{}", self.kind, self.namespace, self.name)
+    }
+}
+
+impl Objects {
+    /// List the workloads of every namespace. Pods, ReplicaSets and Jobs
+    /// created by a controller that is listed as well are left to it.
+    pub async fn list_workloads(&mut self, client: Client) -> Result<()> {
+        let list_params = ListParams::default();
+        let pods: Api<Pod> = Api::all(client.clone());
+        let deployments: Api<Deployment> = Api::all(client.clone());
+        let replica_sets: Api<ReplicaSet> = Api::all(client.clone());
+        let replication_controllers: Api<ReplicationController> = Api::all(client.clone());
+        let daemon_sets: Api<DaemonSet> = Api::all(client.clone());
+        let stateful_sets: Api<StatefulSet> = Api::all(client.clone());
+        let jobs: Api<Job> = Api::all(client.clone());
+        let cron_jobs: Api<CronJob> = Api::all(client);
+        for pod in pods.list(&list_params).await?.items {
+            if !controlled_by(&pod, &POD_CONTROLLERS) {
+                self.push_workload("Pod", &pod, pod.spec.as_ref());
+            }
+        }
+        for deployment in deployments.list(&list_params).await?.items {
+            self.add_deployment(&deployment);
+        }
+        for replica_set in replica_sets.list(&list_params).await?.items {
+            if !controlled_by(&replica_set, &["Deployment"]) {
+                self.add_replica_set(&replica_set);
+            }
+        }
+        for controller in replication_controllers.list(&list_params).await?.items {
+            self.add_replication_controller(&controller);
+        }
+        for daemon_set in daemon_sets.list(&list_params).await?.items {
+            self.add_daemon_set(&daemon_set);
+        }
+        for stateful_set in stateful_sets.list(&list_params).await?.items {
+            self.add_stateful_set(&stateful_set);
+        }
+        for job in jobs.list(&list_params).await?.items {
+            if !controlled_by(&job, &["CronJob"]) {
+                self.add_job(&job);
+            }
+        }
+        for cron_job in cron_jobs.list(&list_params).await?.items {
+            self.add_cron_job(&cron_job);
+        }
+        Ok(())
+    }
+
+    /// Add a workload read from a manifest. One that doesn't parse is skipped
+    /// with a warning, it must not cost the RBAC objects of its file.
+    pub(crate) fn add_workload_value(&mut self, kind: &str, object: Mapping) {
+        let value = Value::from(object);
+        let added = match kind {
+            "Pod" => serde_yaml::from_value(value).map(|pod: Pod| {
+                self.push_workload("Pod", &pod, pod.spec.as_ref());
+            }),
+            "Deployment" => serde_yaml::from_value(value).map(|d| self.add_deployment(&d)),
+            "ReplicaSet" => serde_yaml::from_value(value).map(|r| self.add_replica_set(&r)),
+            "ReplicationController" => {
+                serde_yaml::from_value(value).map(|r| self.add_replication_controller(&r))
+            }
+            "DaemonSet" => serde_yaml::from_value(value).map(|d| self.add_daemon_set(&d)),
+            "StatefulSet" => serde_yaml::from_value(value).map(|s| self.add_stateful_set(&s)),
+            "Job" => serde_yaml::from_value(value).map(|job| self.add_job(&job)),
+            "CronJob" => serde_yaml::from_value(value).map(|job| self.add_cron_job(&job)),
+            _ => Ok(()),
+        };
+        if let Err(err) = added {
+            warn!("Skip {}: {}", kind, err);
+        }
+    }
+
+    fn add_deployment(&mut self, deployment: &Deployment) {
+        let spec = deployment
+            .spec
+            .as_ref()
+            .and_then(|spec| spec.template.spec.as_ref());
+        self.push_workload("Deployment", deployment, spec);
+    }
+
+    fn add_replica_set(&mut self, replica_set: &ReplicaSet) {
+        let spec = replica_set
+            .spec
+            .as_ref()
+            .and_then(|spec| spec.template.as_ref())
+            .and_then(|template| template.spec.as_ref());
+        self.push_workload("ReplicaSet", replica_set, spec);
+    }
+
+    fn add_replication_controller(&mut self, controller: &ReplicationController) {
+        let spec = controller
+            .spec
+            .as_ref()
+            .and_then(|spec| spec.template.as_ref())
+            .and_then(|template| template.spec.as_ref());
+        self.push_workload("ReplicationController", controller, spec);
+    }
+
+    fn add_daemon_set(&mut self, daemon_set: &DaemonSet) {
+        let spec = daemon_set
+            .spec
+            .as_ref()
+            .and_then(|spec| spec.template.spec.as_ref());
+        self.push_workload("DaemonSet", daemon_set, spec);
+    }
+
+    fn add_stateful_set(&mut self, stateful_set: &StatefulSet) {
+        let spec = stateful_set
+            .spec
+            .as_ref()
+            .and_then(|spec| spec.template.spec.as_ref());
+        self.push_workload("StatefulSet", stateful_set, spec);
+    }
+
+    fn add_job(&mut self, job: &Job) {
+        let spec = job
+            .spec
+            .as_ref()
+            .and_then(|spec| spec.template.spec.as_ref());
+        self.push_workload("Job", job, spec);
+    }
+
+    fn add_cron_job(&mut self, cron_job: &CronJob) {
+        let spec = cron_job
+            .spec
+            .as_ref()
+            .and_then(|spec| spec.job_template.spec.as_ref())
+            .and_then(|spec| spec.template.spec.as_ref());
+        self.push_workload("CronJob", cron_job, spec);
+    }
+
+    fn push_workload<K: ResourceExt>(&mut self, kind: &str, object: &K, spec: Option<&PodSpec>) {
+        if let Some(spec) = spec {
+            let namespace = object.namespace().unwrap_or("default".to_string());
+            self.workloads.push(Workload::from_pod_spec(
+                kind,
+                object.name_any(),
+                namespace,
+                spec,
+            ));
+        }
+    }
+}
+
+/// Whether the managing controller of `object` is one of `kinds`. Owners
+/// that are not the controller, e.g. garbage collection owners, don't count.
+fn controlled_by<K: ResourceExt>(object: &K, kinds: &[&str]) -> bool {
+    object
+        .owner_references()
+        .iter()
+        .any(|owner| owner.controller == Some(true) && kinds.contains(&owner.kind.as_str()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_workloads() {
+        let mut objects = Objects::default();
+        objects
+            .add_manifest(
+                r#"
+apiVersion: apps/v1
+kind: Deployment
+metadata:
+  name: web
+  namespace: ci
+spec:
+  selector: {matchLabels: {app: web}}
+  template:
+    metadata: {labels: {app: web}}
+    spec:
+      serviceAccountName: deployer
+      initContainers: [{name: init, image: "busybox:1.36"}]
+      containers: [{name: web, image: "nginx:1.25"}]
+---
+apiVersion: batch/v1
+kind: CronJob
+metadata:
+  name: cleanup
+spec:
+  schedule: "@daily"
+  jobTemplate:
+    spec:
+      template:
+        spec:
+          automountServiceAccountToken: false
+          restartPolicy: Never
+          containers: [{name: cleanup, image: "alpine:3"}]
+"#,
+                None,
+            )
+            .unwrap();
+
+        let web = &objects.workloads[0];
+        assert_eq!(web.to_string(), "Deployment ci/web");
+        assert_eq!(web.images, vec!["busybox:1.36", "nginx:1.25"]);
+        let cleanup = &objects.workloads[1];
+        assert_eq!(
+            (cleanup.service_account.as_str(), cleanup.automount_token),
+            ("default", Some(false))
+        );
+
+        let subject = |kind: SubKind, name: &str, namespace: Option<&str>| SubjectInfo {
+            kind,
+            name: name.to_string(),
+            namespace: namespace.map(|ns| ns.to_string()),
+            api_group: None,
+        };
+        assert!(web.runs_as(
+            &subject(SubKind::ServiceAccount, "deployer", None),
+            Some("ci")
+        ));
+        assert!(!web.runs_as(
+            &subject(SubKind::ServiceAccount, "deployer", Some("prod")),
+            None
+        ));
+        assert!(web.runs_as(
+            &subject(SubKind::Group, "system:serviceaccounts:ci", None),
+            None
+        ));
+        assert!(cleanup.runs_as(
+            &subject(SubKind::User, "system:serviceaccount:default:default", None),
+            None
+        ));
+    }
+
+    #[test]
+    fn test_controlled_by() {
+        let pod = |owner: &str| -> Pod {
+            serde_yaml::from_str(&format!(
+                r#"
+metadata:
+  name: web-1
+  ownerReferences:
+    - {{apiVersion: v1, kind: {owner}, name: web, uid: "1", controller: true}}
+"#
+            ))
+            .unwrap()
+        };
+        assert!(controlled_by(&pod("ReplicaSet"), &POD_CONTROLLERS));
+        assert!(controlled_by(&pod("Job"), &POD_CONTROLLERS));
+        assert!(!controlled_by(&pod("Rollout"), &POD_CONTROLLERS));
+
+        let mut owned: Pod = pod("ReplicaSet");
+        owned.metadata.owner_references.as_mut().unwrap()[0].controller = None;
+        assert!(!controlled_by(&owned, &POD_CONTROLLERS));
+    }
+}